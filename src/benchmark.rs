@@ -0,0 +1,431 @@
+// SPDX-FileCopyrightText: © 2023 Merqury Cybersecurity Ltd <info@merqury.eu>
+// SPDX-License-Identifier: PolyForm-Noncommercial-1.0.0
+
+use crate::metrics;
+use clap::value_parser;
+use etsi_gs_qkd_014_testsuite::{
+    common::{
+        self,
+        client::{AsyncClient, AsyncRequestBuilder},
+        config::{Side, CONFIG},
+        error::Error,
+        key_material,
+        spec::SpecVersion,
+    },
+    models::{key::KeyContainer, key_request::KeyRequest},
+};
+use serde::Serialize;
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    num::NonZeroUsize,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+use tokio::{
+    sync::Semaphore,
+    time::{self, MissedTickBehavior},
+};
+
+#[derive(clap::Args)]
+pub struct Args {
+    /// Duration of the benchmark, in seconds.
+    #[arg(
+        long,
+        default_value_t = 60,
+        value_parser = value_parser!(u64).range(1..)
+    )]
+    duration: u64,
+    /// Maximum number of key requests in flight at any one time. Defaults to
+    /// `ETSI_014_TEST_SUITE_MAX_CONCURRENCY`.
+    #[arg(long)]
+    concurrency: Option<NonZeroUsize>,
+    /// Number of key requests started per second. When not set, key requests
+    /// are issued back to back, limited only by the concurrency.
+    #[arg(long, value_parser = parse_rate)]
+    rate: Option<f64>,
+    /// Number of keys requested by each `enc_keys` call. Defaults to, and
+    /// cannot exceed, the `max_key_per_request` reported by `/status`.
+    #[arg(long, value_parser = value_parser!(i64).range(1..))]
+    keys_per_request: Option<i64>,
+    /// Size of the requested keys, in bits. Defaults to the `key_size`
+    /// reported by `/status`, and must be a multiple of 8 within its
+    /// `min_key_size` and `max_key_size`.
+    #[arg(long, value_parser = value_parser!(i64).range(1..))]
+    size: Option<i64>,
+    /// Path of a file to which the report is written in JSON format.
+    #[arg(long)]
+    json: Option<PathBuf>,
+}
+
+#[derive(Serialize)]
+struct Report {
+    spec_version: SpecVersion,
+    duration_secs: f64,
+    concurrency: usize,
+    rate: Option<f64>,
+    keys_per_request: i64,
+    key_size: i64,
+    endpoints: Vec<EndpointReport>,
+}
+
+#[derive(Serialize)]
+struct EndpointReport {
+    endpoint: &'static str,
+    requests: usize,
+    successful_requests: usize,
+    requests_per_second: f64,
+    key_bits_per_second: f64,
+    latency_ms: LatencyReport,
+    errors: BTreeMap<String, usize>,
+}
+
+#[derive(Serialize)]
+struct LatencyReport {
+    p50: f64,
+    p95: f64,
+    p99: f64,
+}
+
+#[derive(Default)]
+struct EndpointStats {
+    latencies: Vec<Duration>,
+    successful_requests: usize,
+    key_bits: u64,
+    errors: BTreeMap<String, usize>,
+}
+
+#[derive(Default)]
+struct Stats {
+    enc_keys: EndpointStats,
+    dec_keys: EndpointStats,
+}
+
+struct Workload {
+    master_client: AsyncClient,
+    slave_client: AsyncClient,
+    enc_keys_url: String,
+    dec_keys_url: String,
+    keys_per_request: i64,
+    key_size: i64,
+    stats: Mutex<Stats>,
+}
+
+pub async fn run(args: Args) -> Result<(), Error> {
+    let master_client =
+        common::build_async_client(CONFIG.master(), Side::Master)?;
+    let slave_client = common::build_async_client(CONFIG.slave(), Side::Slave)?;
+    let status = metrics::fetch_status(&master_client).await?;
+    let concurrency = args.concurrency.unwrap_or(CONFIG.max_concurrency).get();
+    let keys_per_request = match args.keys_per_request {
+        Some(val) if val > status.max_key_per_request => {
+            eprintln!(
+                "Requested {} keys per request, but the KME supports at most \
+                 {}. Using {} instead.",
+                val, status.max_key_per_request, status.max_key_per_request
+            );
+            status.max_key_per_request
+        }
+        Some(val) => val,
+        None => status.max_key_per_request,
+    };
+    let key_size = args.size.unwrap_or(status.key_size);
+
+    if keys_per_request < 1 {
+        return Err(Error::Protocol {
+            url: format!("{}/{}/status", CONFIG.base_url, CONFIG.slave().id),
+            reason: format!(
+                "cannot request keys, as 'max_key_per_request' is {}",
+                keys_per_request
+            ),
+        });
+    }
+
+    if key_size < status.min_key_size || key_size > status.max_key_size {
+        return Err(Error::Config(format!(
+            "cannot request {}-bit keys, as the KME supports {} to {} bits",
+            key_size, status.min_key_size, status.max_key_size
+        )));
+    }
+
+    if key_size % 8 != 0 {
+        return Err(Error::Config(format!(
+            "cannot request {}-bit keys, as the size is not a multiple of 8",
+            key_size
+        )));
+    }
+
+    let workload = Arc::new(Workload {
+        master_client,
+        slave_client,
+        enc_keys_url: format!(
+            "{}/{}/enc_keys",
+            CONFIG.base_url,
+            CONFIG.slave().id
+        ),
+        dec_keys_url: format!(
+            "{}/{}/dec_keys",
+            CONFIG.slave_base_url,
+            CONFIG.master().id
+        ),
+        keys_per_request,
+        key_size,
+        stats: Mutex::new(Stats::default()),
+    });
+
+    let duration = Duration::from_secs(args.duration);
+    let start = Instant::now();
+
+    match args.rate {
+        Some(rate) => {
+            run_open_loop(&workload, start + duration, concurrency, rate).await
+        }
+        None => run_closed_loop(&workload, start + duration, concurrency).await,
+    }
+
+    let elapsed = start.elapsed().as_secs_f64();
+    let stats = std::mem::take(&mut *workload.stats.lock().unwrap());
+    let report = Report {
+        spec_version: CONFIG.spec_version,
+        duration_secs: elapsed,
+        concurrency,
+        rate: args.rate,
+        keys_per_request,
+        key_size,
+        endpoints: vec![
+            stats.enc_keys.into_report("enc_keys", elapsed),
+            stats.dec_keys.into_report("dec_keys", elapsed),
+        ],
+    };
+
+    print_report(&report);
+
+    if let Some(path) = args.json {
+        write_report(&path, &report)?;
+    }
+
+    Ok(())
+}
+
+fn parse_rate(value: &str) -> Result<f64, String> {
+    match value.parse::<f64>() {
+        Ok(rate) if rate.is_finite() && rate > 0.0 => Ok(rate),
+        Ok(_) => Err("the rate must be greater than 0".to_string()),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+fn write_report(path: &Path, report: &Report) -> Result<(), Error> {
+    let report_error = |reason: String| {
+        Error::Config(format!(
+            "cannot write the report '{}': {}",
+            path.display(),
+            reason
+        ))
+    };
+    let file = File::create(path).map_err(|e| report_error(e.to_string()))?;
+
+    serde_json::to_writer_pretty(file, report)
+        .map_err(|e| report_error(e.to_string()))
+}
+
+/// Keeps `concurrency` key requests in flight until the deadline is reached.
+async fn run_closed_loop(
+    workload: &Arc<Workload>,
+    deadline: Instant,
+    concurrency: usize,
+) {
+    let workers = (0..concurrency).map(|_| {
+        let workload = Arc::clone(workload);
+
+        tokio::spawn(async move {
+            while Instant::now() < deadline {
+                workload.request_and_retrieve_keys(Instant::now()).await;
+            }
+        })
+    });
+
+    for worker in futures::future::join_all(workers).await {
+        worker.unwrap();
+    }
+}
+
+/// Starts `rate` key requests per second until the deadline is reached, with
+/// at most `concurrency` of them in flight at any one time.
+///
+/// Latencies are measured from the time at which each key request was
+/// scheduled, so that requests delayed by a slow KME, or by the concurrency
+/// limit, are not left out of the measurement (coordinated omission).
+async fn run_open_loop(
+    workload: &Arc<Workload>,
+    deadline: Instant,
+    concurrency: usize,
+    rate: f64,
+) {
+    let semaphore = Arc::new(Semaphore::new(concurrency));
+    let mut interval = time::interval(Duration::from_secs_f64(1.0 / rate));
+    let mut tasks = Vec::new();
+
+    // Missed ticks are caught up on, so that the schedule does not slow down
+    // along with the KME.
+    interval.set_missed_tick_behavior(MissedTickBehavior::Burst);
+
+    loop {
+        let scheduled = interval.tick().await.into_std();
+
+        if scheduled >= deadline {
+            break;
+        }
+
+        let permit = Arc::clone(&semaphore).acquire_owned().await.unwrap();
+        let workload = Arc::clone(workload);
+
+        tasks.push(tokio::spawn(async move {
+            workload.request_and_retrieve_keys(scheduled).await;
+            drop(permit);
+        }));
+    }
+
+    for task in futures::future::join_all(tasks).await {
+        task.unwrap();
+    }
+}
+
+impl Workload {
+    /// Requests keys as the master SAE and, if successful, retrieves them as
+    /// the slave SAE. The latency of the key request is measured from
+    /// `scheduled`.
+    async fn request_and_retrieve_keys(&self, scheduled: Instant) {
+        let enc_keys_request =
+            self.master_client.post(&self.enc_keys_url).json(&KeyRequest {
+                number: Some(self.keys_per_request),
+                size: Some(self.key_size),
+                ..Default::default()
+            });
+        let returned_keys = match self
+            .send(enc_keys_request, Endpoint::EncKeys, scheduled)
+            .await
+        {
+            Some(val) => val,
+            None => return,
+        };
+
+        let dec_keys_request =
+            self.slave_client.post(&self.dec_keys_url).json(&returned_keys);
+
+        self.send(dec_keys_request, Endpoint::DecKeys, Instant::now()).await;
+    }
+
+    async fn send(
+        &self,
+        request: AsyncRequestBuilder,
+        endpoint: Endpoint,
+        start: Instant,
+    ) -> Option<KeyContainer> {
+        let result = metrics::send_key_request(request).await;
+        let latency = start.elapsed();
+
+        let mut stats = self.stats.lock().unwrap();
+        let endpoint_stats = match endpoint {
+            Endpoint::EncKeys => &mut stats.enc_keys,
+            Endpoint::DecKeys => &mut stats.dec_keys,
+        };
+
+        endpoint_stats.latencies.push(latency);
+
+        match result {
+            Ok(key_container) => {
+                endpoint_stats.successful_requests += 1;
+                endpoint_stats.key_bits += key_bits(&key_container);
+
+                Some(key_container)
+            }
+            Err(label) => {
+                *endpoint_stats.errors.entry(label).or_default() += 1;
+
+                None
+            }
+        }
+    }
+}
+
+/// Number of bits of key material delivered, as decoded. Keys that cannot be
+/// decoded are not counted.
+fn key_bits(key_container: &KeyContainer) -> u64 {
+    key_container
+        .keys
+        .iter()
+        .filter_map(|key| key.key.as_deref())
+        .filter_map(|key| key_material::decode(key).ok())
+        .map(|(bytes, _)| bytes.len() as u64 * 8)
+        .sum()
+}
+
+enum Endpoint {
+    EncKeys,
+    DecKeys,
+}
+
+impl EndpointStats {
+    fn into_report(
+        mut self,
+        endpoint: &'static str,
+        elapsed_secs: f64,
+    ) -> EndpointReport {
+        self.latencies.sort();
+
+        EndpointReport {
+            endpoint,
+            requests: self.latencies.len(),
+            successful_requests: self.successful_requests,
+            requests_per_second: self.latencies.len() as f64 / elapsed_secs,
+            key_bits_per_second: self.key_bits as f64 / elapsed_secs,
+            latency_ms: LatencyReport {
+                p50: metrics::percentile(&self.latencies, 50.0),
+                p95: metrics::percentile(&self.latencies, 95.0),
+                p99: metrics::percentile(&self.latencies, 99.0),
+            },
+            errors: self.errors,
+        }
+    }
+}
+
+fn print_report(report: &Report) {
+    println!(
+        "Spec version: {}, duration: {:.1}s, concurrency: {}, rate: {}, \
+         keys per request: {}, key size: {} bits",
+        report.spec_version,
+        report.duration_secs,
+        report.concurrency,
+        report
+            .rate
+            .map_or("unlimited".to_string(), |rate| format!("{}/s", rate)),
+        report.keys_per_request,
+        report.key_size
+    );
+
+    for endpoint in &report.endpoints {
+        println!();
+        println!("{}", endpoint.endpoint);
+        println!(
+            "  requests:     {} ({} successful)",
+            endpoint.requests, endpoint.successful_requests
+        );
+        println!(
+            "  throughput:   {:.2} requests/s",
+            endpoint.requests_per_second
+        );
+        println!("  key rate:     {:.0} bits/s", endpoint.key_bits_per_second);
+        println!(
+            "  latency:      p50 {:.2}ms, p95 {:.2}ms, p99 {:.2}ms",
+            endpoint.latency_ms.p50,
+            endpoint.latency_ms.p95,
+            endpoint.latency_ms.p99
+        );
+
+        for (error, count) in &endpoint.errors {
+            println!("  error:        {} x {}", error, count);
+        }
+    }
+}
@@ -0,0 +1,195 @@
+// SPDX-FileCopyrightText: © 2023 Merqury Cybersecurity Ltd <info@merqury.eu>
+// SPDX-License-Identifier: PolyForm-Noncommercial-1.0.0
+
+//! Discovery of the limits of the KME under test, from which the tests derive
+//! the key sizes and counts they request, rather than hard-coding values that
+//! not every KME supports.
+
+use super::{
+    config::{Side, CONFIG},
+    error::Error,
+};
+use crate::models::status::Status;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use std::{
+    io::{self, Write},
+    thread,
+};
+
+/// Number of random key sizes checked in addition to the minimum, maximum and
+/// default ones.
+static NUM_RANDOM_KEY_SIZES: usize = 3;
+
+lazy_static! {
+    pub static ref CAPABILITIES: Capabilities = Capabilities::discover();
+}
+
+#[derive(Debug, Clone)]
+pub struct Capabilities {
+    /// The `/status` reply of the master SAE for the slave SAE.
+    pub status: Status,
+    /// Seed used to pick the random key sizes and counts.
+    pub seed: u64,
+}
+
+impl Capabilities {
+    /// Queries `/status` as the master SAE. Panics if the KME does not reply
+    /// with a valid status, as no other check can be parametrised without it.
+    ///
+    /// The query is made from a thread of its own, so that it is recorded
+    /// as the `capability_discovery` check rather than as part of whichever
+    /// check happens to run first.
+    pub fn discover() -> Self {
+        let discovery = thread::Builder::new()
+            .name("capability_discovery".to_string())
+            .spawn(Self::query_status)
+            .unwrap();
+
+        let status = match discovery.join() {
+            Ok(Ok(val)) => val,
+            Ok(Err(e)) => panic!("Capability discovery failed: {}", e),
+            Err(e) => std::panic::resume_unwind(e),
+        };
+
+        let seed = CONFIG.seed.unwrap_or_else(rand::random);
+
+        report(&format!("Spec version: {}", CONFIG.spec_version));
+        report(&format!("Capability discovery seed: {}", seed));
+
+        Self::new(status, seed)
+    }
+
+    fn query_status() -> Result<Status, Error> {
+        let client = super::build_client(CONFIG.master(), Side::Master)?;
+        let url = format!("{}/{}/status", CONFIG.base_url, CONFIG.slave().id);
+        let response = client.get(&url).send()?;
+
+        if !response.status().is_success() {
+            return Err(Error::Protocol {
+                url,
+                reason: format!(
+                    "failed to retrieve '/status', returned status {}",
+                    response.status()
+                ),
+            });
+        }
+
+        response.json::<Status>()
+    }
+
+    pub fn new(status: Status, seed: u64) -> Self {
+        Self { status, seed }
+    }
+
+    /// Key sizes, in bits, to request: the minimum, maximum and default ones,
+    /// plus a few random multiples of 8 in between. Sizes are sorted and
+    /// deduplicated.
+    pub fn key_sizes(&self) -> Vec<i64> {
+        let mut rng = StdRng::seed_from_u64(self.seed);
+        let mut sizes = vec![
+            self.status.min_key_size,
+            self.status.max_key_size,
+            self.status.key_size,
+        ];
+        let lowest = (self.status.min_key_size.max(8) + 7) / 8;
+        let highest = self.status.max_key_size / 8;
+
+        if lowest <= highest {
+            sizes.extend(
+                (0..NUM_RANDOM_KEY_SIZES)
+                    .map(|_| rng.gen_range(lowest..=highest) * 8),
+            );
+        }
+
+        sizes.sort_unstable();
+        sizes.dedup();
+
+        sizes
+    }
+
+    /// Every key size, in bits, from the first multiple of 8 not below the
+    /// minimum size up to the maximum size, `stride` bits apart.
+    pub fn key_size_sweep(&self, stride: usize) -> Vec<i64> {
+        let first = (self.status.min_key_size.max(1) + 7) / 8 * 8;
+
+        (first..=self.status.max_key_size).step_by(stride).collect()
+    }
+
+    /// Numbers of keys to request at once: one, the maximum and a random
+    /// number in between.
+    pub fn key_counts(&self) -> Vec<i64> {
+        let mut rng = StdRng::seed_from_u64(self.seed.wrapping_add(1));
+        let mut counts = vec![1, self.status.max_key_per_request];
+
+        if self.status.max_key_per_request > 1 {
+            counts.push(rng.gen_range(1..=self.status.max_key_per_request));
+        }
+
+        counts.sort_unstable();
+        counts.dedup();
+
+        counts
+    }
+
+    /// Returns why requesting `number` keys of `size` bits cannot succeed on
+    /// this KME, or `None` if the request is within its limits.
+    pub fn unsupported_reason(&self, number: i64, size: i64) -> Option<String> {
+        let status = &self.status;
+
+        if number < 1 || number > status.max_key_per_request {
+            Some(format!(
+                "{} keys requested, but the KME supports 1 to {} keys per \
+                 request",
+                number, status.max_key_per_request
+            ))
+        } else if size < status.min_key_size || size > status.max_key_size {
+            Some(format!(
+                "{}-bit keys requested, but the KME supports {} to {} bits",
+                size, status.min_key_size, status.max_key_size
+            ))
+        } else if size % 8 != 0 {
+            Some(format!(
+                "{}-bit keys requested, but the size is not a multiple of 8",
+                size
+            ))
+        } else {
+            None
+        }
+    }
+
+    /// Returns why a key cannot be shared with `count` additional slave SAEs
+    /// on this KME, or `None` if it can.
+    pub fn unsupported_additional_slaves_reason(
+        &self,
+        count: i64,
+    ) -> Option<String> {
+        if count > self.status.max_sae_id_count {
+            Some(format!(
+                "{} additional slave SAEs requested, but the KME supports at \
+                 most {}",
+                count, self.status.max_sae_id_count
+            ))
+        } else if count as usize > CONFIG.extra_slaves().len() {
+            Some(format!(
+                "{} additional slave SAEs requested, but only {} are \
+                 configured",
+                count,
+                CONFIG.extra_slaves().len()
+            ))
+        } else {
+            None
+        }
+    }
+}
+
+/// Reports that the current check, or part of it, was skipped.
+pub fn skip(reason: &str) {
+    report(&format!("Skipped '{}': {}", super::check_name(), reason));
+}
+
+/// Prints a line to the standard error. The test harness captures only what is
+/// printed through the `print!` family of macros, so the line is written to
+/// the stream directly to be shown whether the check passes or not.
+fn report(line: &str) {
+    let _ = writeln!(io::stderr(), "{}", line);
+}
@@ -0,0 +1,296 @@
+// SPDX-FileCopyrightText: © 2023 Merqury Cybersecurity Ltd <info@merqury.eu>
+// SPDX-License-Identifier: PolyForm-Noncommercial-1.0.0
+
+//! Reading and inspection of the X.509 certificates configured for the test
+//! suite, so that expired certificates and certificates issued to another SAE
+//! can be reported before any request is sent.
+//!
+//! SAE identities can be given as PEM, with the private key in the certificate
+//! file or apart, as DER, or as PKCS#12 bundles, and private keys may be
+//! encrypted. They are converted to PEM, as `rustls` accepts nothing else.
+
+use super::{config::SaeIdentity, error::Error};
+use openssl::{
+    asn1::Asn1Time,
+    nid::Nid,
+    pkcs12::Pkcs12,
+    pkey::{PKey, Private},
+    x509::X509,
+};
+use std::fs;
+use zeroize::Zeroizing;
+
+static PEM_LABEL: &[u8] = b"-----BEGIN ";
+static ENCRYPTED_PEM_LABELS: [&[u8]; 2] =
+    [b"ENCRYPTED PRIVATE KEY", b"Proc-Type: 4,ENCRYPTED"];
+
+/// The certificate chain and private key of an SAE.
+pub struct Identity {
+    /// The certificate of the SAE, followed by the rest of its chain.
+    pub chain: Vec<X509>,
+    key: PKey<Private>,
+}
+
+impl Identity {
+    /// Reads the identity of an SAE from its certificate file and, if set,
+    /// its private key file, decrypting either with the SAE password.
+    pub fn read(sae: &SaeIdentity) -> Result<Self, Error> {
+        let password = sae.password.as_deref().unwrap_or_default();
+        let crt = read(&sae.crt)?;
+        let (chain, bundled_key) = match is_pem(&crt) {
+            true => (
+                X509::stack_from_pem(&crt).map_err(|e| {
+                    certificate_error(&sae.crt, format!("not valid PEM: {}", e))
+                })?,
+                None,
+            ),
+            false => Self::read_der(&sae.crt, &crt, password)?,
+        };
+
+        if chain.is_empty() {
+            return Err(certificate_error(
+                &sae.crt,
+                "holds no certificate".to_string(),
+            ));
+        }
+
+        let key = match (&sae.key, bundled_key) {
+            (Some(path), _) => read_private_key(path, &read(path)?, password)?,
+            (None, Some(val)) => val,
+            (None, None) if is_pem(&crt) => {
+                read_private_key(&sae.crt, &crt, password)?
+            }
+            (None, None) => {
+                return Err(certificate_error(
+                    &sae.crt,
+                    "holds no private key and no key file is set".to_string(),
+                ))
+            }
+        };
+        let path = sae.key.as_ref().unwrap_or(&sae.crt);
+        let matches = chain[0]
+            .public_key()
+            .map(|val| val.public_eq(&key))
+            .map_err(|e| certificate_error(path, e.to_string()))?;
+
+        if !matches {
+            return Err(certificate_error(
+                path,
+                format!(
+                    "the private key does not match the certificate of SAE \
+                     '{}'",
+                    sae.id
+                ),
+            ));
+        }
+
+        Ok(Self { chain, key })
+    }
+
+    /// Reads a PKCS#12 bundle, or a single DER certificate.
+    fn read_der(
+        path: &str,
+        der: &[u8],
+        password: &str,
+    ) -> Result<(Vec<X509>, Option<PKey<Private>>), Error> {
+        if let Ok(bundle) = Pkcs12::from_der(der) {
+            let parsed = bundle.parse2(password).map_err(|e| {
+                certificate_error(
+                    path,
+                    format!("cannot decrypt the PKCS#12 bundle: {}", e),
+                )
+            })?;
+            let chain = parsed
+                .cert
+                .into_iter()
+                .chain(parsed.ca.into_iter().flatten())
+                .collect();
+
+            return Ok((chain, parsed.pkey));
+        }
+
+        match X509::from_der(der) {
+            Ok(val) => Ok((vec![val], None)),
+            Err(_) => Err(certificate_error(
+                path,
+                "neither PEM, PKCS#12 nor DER".to_string(),
+            )),
+        }
+    }
+
+    /// The certificate of the SAE.
+    pub fn certificate(&self) -> Result<Certificate, String> {
+        Certificate::new(&self.chain[0])
+    }
+
+    /// The chain and unencrypted private key, as PEM.
+    pub fn to_pem(&self) -> Result<Zeroizing<Vec<u8>>, String> {
+        let mut pem = Zeroizing::new(Vec::new());
+
+        for x509 in &self.chain {
+            pem.extend(x509.to_pem().map_err(|e| e.to_string())?);
+        }
+
+        pem.extend(
+            Zeroizing::new(
+                self.key
+                    .private_key_to_pem_pkcs8()
+                    .map_err(|e| e.to_string())?,
+            )
+            .iter(),
+        );
+
+        Ok(pem)
+    }
+}
+
+/// The details of a certificate relevant to the checks.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Certificate {
+    /// The subject, such as `CN=sae_001, O=Example`.
+    pub subject: String,
+    /// The common name of the subject, if any.
+    pub common_name: Option<String>,
+    /// The DNS names, URIs and email addresses of the subject alternative
+    /// name extension.
+    pub alt_names: Vec<String>,
+    pub not_before: String,
+    pub not_after: String,
+    /// Whole days left until the certificate expires, negative once it has.
+    pub days_left: i32,
+    /// Whether the certificate has expired.
+    pub expired: bool,
+    /// Whether the certificate is not valid yet.
+    pub not_yet_valid: bool,
+}
+
+impl Certificate {
+    /// Reads the first certificate of a PEM file, which is the certificate
+    /// of the holder when the file holds a chain.
+    pub fn read(path: &str) -> Result<Self, Error> {
+        let pem = read(path)?;
+
+        X509::stack_from_pem(&pem)
+            .map_err(|e| {
+                certificate_error(path, format!("not valid PEM: {}", e))
+            })?
+            .first()
+            .ok_or_else(|| {
+                certificate_error(path, "holds no certificate".to_string())
+            })
+            .and_then(|val| {
+                Self::new(val).map_err(|e| certificate_error(path, e))
+            })
+    }
+
+    pub fn new(x509: &X509) -> Result<Self, String> {
+        let now = Asn1Time::days_from_now(0).map_err(|e| e.to_string())?;
+        let left = now.diff(x509.not_after()).map_err(|e| e.to_string())?;
+        let not_yet_valid = now
+            .diff(x509.not_before())
+            .map(|val| val.days > 0 || val.secs > 0)
+            .map_err(|e| e.to_string())?;
+        let subject = x509
+            .subject_name()
+            .entries()
+            .map(|entry| {
+                format!(
+                    "{}={}",
+                    entry.object().nid().short_name().unwrap_or("?"),
+                    entry.data().to_string().unwrap_or_default()
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+        let common_name = x509
+            .subject_name()
+            .entries_by_nid(Nid::COMMONNAME)
+            .next()
+            .and_then(|entry| entry.data().to_string().ok());
+        let alt_names = x509
+            .subject_alt_names()
+            .into_iter()
+            .flatten()
+            .filter_map(|name| {
+                name.dnsname()
+                    .or_else(|| name.uri())
+                    .or_else(|| name.email())
+                    .map(String::from)
+            })
+            .collect();
+
+        Ok(Self {
+            subject,
+            common_name,
+            alt_names,
+            not_before: x509.not_before().to_string(),
+            not_after: x509.not_after().to_string(),
+            days_left: left.days,
+            expired: left.days < 0 || left.secs < 0,
+            not_yet_valid,
+        })
+    }
+
+    /// Whether the certificate is issued to the SAE, i.e. whether its common
+    /// name or one of its alternative names is the SAE ID.
+    pub fn names_sae(&self, sae_id: &str) -> bool {
+        self.common_name.as_deref() == Some(sae_id)
+            || self.alt_names.iter().any(|name| name == sae_id)
+    }
+}
+
+fn read(path: &str) -> Result<Vec<u8>, Error> {
+    fs::read(path)
+        .map_err(|e| certificate_error(path, format!("cannot be read: {}", e)))
+}
+
+fn is_pem(content: &[u8]) -> bool {
+    contains(content, PEM_LABEL)
+}
+
+fn contains(content: &[u8], pattern: &[u8]) -> bool {
+    content.windows(pattern.len()).any(|val| val == pattern)
+}
+
+/// Reads a private key, as PEM or DER. A password is always passed, even if
+/// empty, so that OpenSSL never prompts for one.
+fn read_private_key(
+    path: &str,
+    content: &[u8],
+    password: &str,
+) -> Result<PKey<Private>, Error> {
+    let key = match is_pem(content) {
+        true => {
+            PKey::private_key_from_pem_passphrase(content, password.as_bytes())
+        }
+        false => PKey::private_key_from_pkcs8_passphrase(
+            content,
+            password.as_bytes(),
+        )
+        .or_else(|_| PKey::private_key_from_der(content)),
+    };
+
+    key.map_err(|e| {
+        let encrypted =
+            ENCRYPTED_PEM_LABELS.iter().any(|label| contains(content, label));
+        let reason = match (encrypted, password.is_empty()) {
+            (true, true) => {
+                "the private key is encrypted, but no password is set"
+                    .to_string()
+            }
+            (true, false) => {
+                format!("cannot decrypt the private key: {}", e)
+            }
+            (false, _) => format!("not a valid private key: {}", e),
+        };
+
+        certificate_error(path, reason)
+    })
+}
+
+fn certificate_error(path: &str, reason: String) -> Error {
+    Error::Certificate {
+        path: path.to_string(),
+        reason,
+    }
+}
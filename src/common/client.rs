@@ -0,0 +1,409 @@
+// SPDX-FileCopyrightText: © 2023 Merqury Cybersecurity Ltd <info@merqury.eu>
+// SPDX-License-Identifier: PolyForm-Noncommercial-1.0.0
+
+//! Thin wrappers around the blocking and asynchronous `reqwest` clients that
+//! capture every response, so that it can be validated against the OpenAPI
+//! document and recorded before being handed to the test. Both go through the
+//! same exchange, so that they are recorded, validated and replayed alike.
+
+use super::{error::Error, har, openapi, replay};
+use reqwest::{
+    blocking::Body,
+    header::{HeaderMap, HeaderName, CONTENT_TYPE},
+    IntoUrl, Method, StatusCode, Url, Version,
+};
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::{error::Category, Value};
+use std::{
+    any::type_name,
+    convert::Infallible,
+    time::{Duration, Instant, SystemTime},
+};
+
+#[derive(Clone)]
+pub struct Client {
+    inner: reqwest::blocking::Client,
+    tls: har::Tls,
+}
+
+pub struct RequestBuilder {
+    client: reqwest::blocking::Client,
+    tls: har::Tls,
+    inner: reqwest::blocking::RequestBuilder,
+}
+
+/// Asynchronous counterpart of [`Client`], used by the workloads that keep
+/// several requests in flight.
+#[derive(Clone)]
+pub struct AsyncClient {
+    inner: reqwest::Client,
+    tls: har::Tls,
+}
+
+pub struct AsyncRequestBuilder {
+    client: reqwest::Client,
+    tls: har::Tls,
+    inner: reqwest::RequestBuilder,
+}
+
+/// A request about to be sent, or replayed.
+struct Exchange {
+    method: Method,
+    url: Url,
+    headers: HeaderMap,
+    body: Option<Vec<u8>>,
+    tls: har::Tls,
+}
+
+/// A response as it was read.
+struct Read {
+    status: StatusCode,
+    version: Version,
+    url: Url,
+    headers: HeaderMap,
+    body: Vec<u8>,
+    /// Time from sending the request to receiving the response headers.
+    wait: Duration,
+}
+
+/// A response whose body has already been read.
+pub struct Response {
+    status: StatusCode,
+    url: Url,
+    headers: HeaderMap,
+    body: Vec<u8>,
+}
+
+impl Client {
+    pub fn new(inner: reqwest::blocking::Client, tls: har::Tls) -> Self {
+        Self { inner, tls }
+    }
+
+    pub fn request<U: IntoUrl>(
+        &self,
+        method: Method,
+        url: U,
+    ) -> RequestBuilder {
+        RequestBuilder {
+            client: self.inner.clone(),
+            tls: self.tls.clone(),
+            inner: self.inner.request(method, url),
+        }
+    }
+
+    pub fn get<U: IntoUrl>(&self, url: U) -> RequestBuilder {
+        self.request(Method::GET, url)
+    }
+
+    pub fn post<U: IntoUrl>(&self, url: U) -> RequestBuilder {
+        self.request(Method::POST, url)
+    }
+}
+
+impl RequestBuilder {
+    pub fn query<T: Serialize + ?Sized>(self, query: &T) -> Self {
+        self.map(|inner| inner.query(query))
+    }
+
+    pub fn json<T: Serialize + ?Sized>(self, json: &T) -> Self {
+        self.map(|inner| inner.json(json))
+    }
+
+    pub fn header(self, key: HeaderName, value: &str) -> Self {
+        self.map(|inner| inner.header(key, value))
+    }
+
+    pub fn body<T: Into<Body>>(self, body: T) -> Self {
+        self.map(|inner| inner.body(body))
+    }
+
+    /// Sends the request and reads the whole response, which is then checked
+    /// against the OpenAPI document. The exchange is recorded, whether it
+    /// succeeds or not. When replaying, the recorded response is used instead
+    /// and nothing is sent, and a recorded failure is returned as such.
+    pub fn send(self) -> Result<Response, Error> {
+        let request = self.inner.build()?;
+        let exchange = Exchange {
+            method: request.method().clone(),
+            url: request.url().clone(),
+            headers: request.headers().clone(),
+            body: request
+                .body()
+                .and_then(|val| val.as_bytes())
+                .map(<[u8]>::to_vec),
+            tls: self.tls,
+        };
+
+        if let Some(replayed) = exchange.replay() {
+            return replayed;
+        }
+
+        let started = SystemTime::now();
+        let sent = Instant::now();
+        let read = self
+            .client
+            .execute(request)
+            .and_then(|response| {
+                let wait = sent.elapsed();
+
+                Ok(Read {
+                    status: response.status(),
+                    version: response.version(),
+                    url: response.url().clone(),
+                    headers: response.headers().clone(),
+                    body: response.bytes()?.to_vec(),
+                    wait,
+                })
+            })
+            .map_err(|e| Error::from_reqwest(&e));
+
+        if let Some(entry) = exchange.entry(started, sent, &read) {
+            har::record(&entry);
+        }
+
+        exchange.checked(read)
+    }
+
+    fn map<F>(self, f: F) -> Self
+    where
+        F: FnOnce(
+            reqwest::blocking::RequestBuilder,
+        ) -> reqwest::blocking::RequestBuilder,
+    {
+        Self {
+            client: self.client,
+            tls: self.tls,
+            inner: f(self.inner),
+        }
+    }
+}
+
+impl AsyncClient {
+    pub fn new(inner: reqwest::Client, tls: har::Tls) -> Self {
+        Self { inner, tls }
+    }
+
+    pub fn request<U: IntoUrl>(
+        &self,
+        method: Method,
+        url: U,
+    ) -> AsyncRequestBuilder {
+        AsyncRequestBuilder {
+            client: self.inner.clone(),
+            tls: self.tls.clone(),
+            inner: self.inner.request(method, url),
+        }
+    }
+
+    pub fn get<U: IntoUrl>(&self, url: U) -> AsyncRequestBuilder {
+        self.request(Method::GET, url)
+    }
+
+    pub fn post<U: IntoUrl>(&self, url: U) -> AsyncRequestBuilder {
+        self.request(Method::POST, url)
+    }
+}
+
+impl AsyncRequestBuilder {
+    pub fn query<T: Serialize + ?Sized>(self, query: &T) -> Self {
+        self.map(|inner| inner.query(query))
+    }
+
+    pub fn json<T: Serialize + ?Sized>(self, json: &T) -> Self {
+        self.map(|inner| inner.json(json))
+    }
+
+    pub fn timeout(self, timeout: Duration) -> Self {
+        self.map(|inner| inner.timeout(timeout))
+    }
+
+    /// Same as [`RequestBuilder::send`].
+    pub async fn send(self) -> Result<Response, Error> {
+        let request = self.inner.build()?;
+        let exchange = Exchange {
+            method: request.method().clone(),
+            url: request.url().clone(),
+            headers: request.headers().clone(),
+            body: request
+                .body()
+                .and_then(|val| val.as_bytes())
+                .map(<[u8]>::to_vec),
+            tls: self.tls,
+        };
+
+        if let Some(replayed) = exchange.replay() {
+            return replayed;
+        }
+
+        let started = SystemTime::now();
+        let sent = Instant::now();
+        let read = async {
+            let response = self.client.execute(request).await?;
+            let wait = sent.elapsed();
+
+            Ok(Read {
+                status: response.status(),
+                version: response.version(),
+                url: response.url().clone(),
+                headers: response.headers().clone(),
+                body: response.bytes().await?.to_vec(),
+                wait,
+            })
+        }
+        .await
+        .map_err(|e: reqwest::Error| Error::from_reqwest(&e));
+
+        if let Some(entry) = exchange.entry(started, sent, &read) {
+            har::record_async(entry).await;
+        }
+
+        exchange.checked(read)
+    }
+
+    fn map<F>(self, f: F) -> Self
+    where
+        F: FnOnce(reqwest::RequestBuilder) -> reqwest::RequestBuilder,
+    {
+        Self {
+            client: self.client,
+            tls: self.tls,
+            inner: f(self.inner),
+        }
+    }
+}
+
+impl Exchange {
+    fn har_request(&self) -> har::Request<'_> {
+        har::Request {
+            method: &self.method,
+            url: &self.url,
+            headers: &self.headers,
+            body: self.body.as_deref(),
+        }
+    }
+
+    /// The recorded response or failure, when replaying.
+    fn replay(&self) -> Option<Result<Response, Error>> {
+        let replay = replay::REPLAY.as_ref()?;
+
+        Some(
+            replay
+                .respond(&self.method, &self.url, self.body.as_deref())
+                .and_then(|recorded| {
+                    Response::checked(
+                        &self.method,
+                        self.url.clone(),
+                        recorded.status,
+                        recorded.headers,
+                        recorded.body,
+                    )
+                }),
+        )
+    }
+
+    /// The HAR entry of the outcome of the request, if recording is enabled.
+    fn entry(
+        &self,
+        started: SystemTime,
+        sent: Instant,
+        read: &Result<Read, Error>,
+    ) -> Option<Value> {
+        let outcome = match read {
+            Ok(read) => har::Outcome::Response {
+                status: read.status,
+                version: read.version,
+                headers: &read.headers,
+                body: &read.body,
+                wait: read.wait,
+                receive: sent.elapsed() - read.wait,
+            },
+            Err(e) => har::Outcome::Error(e),
+        };
+
+        har::recorded_entry(started, self.har_request(), outcome, &self.tls)
+    }
+
+    /// Checks the response, once recorded.
+    fn checked(self, read: Result<Read, Error>) -> Result<Response, Error> {
+        let read = read?;
+
+        Response::checked(
+            &self.method,
+            read.url,
+            read.status,
+            read.headers,
+            read.body,
+        )
+    }
+}
+
+impl Response {
+    /// Checks the response against the OpenAPI document.
+    fn checked(
+        method: &Method,
+        url: Url,
+        status: StatusCode,
+        headers: HeaderMap,
+        body: Vec<u8>,
+    ) -> Result<Self, Error> {
+        openapi::check_response(
+            method,
+            &url,
+            status,
+            headers.get(CONTENT_TYPE).and_then(|val| val.to_str().ok()),
+            &body,
+        )?;
+
+        Ok(Self {
+            status,
+            url,
+            headers,
+            body,
+        })
+    }
+
+    pub fn status(&self) -> StatusCode {
+        self.status
+    }
+
+    pub fn url(&self) -> &Url {
+        &self.url
+    }
+
+    pub fn headers(&self) -> &HeaderMap {
+        &self.headers
+    }
+
+    pub fn bytes(&self) -> &[u8] {
+        &self.body
+    }
+
+    /// Returns the body as text, replacing invalid UTF-8 sequences.
+    pub fn text(self) -> Result<String, Infallible> {
+        Ok(String::from_utf8_lossy(&self.body).into_owned())
+    }
+
+    /// Deserializes the body. The error tells a body that is not JSON apart
+    /// from one that does not match the expected type, and names the field
+    /// that could not be deserialized, such as `keys[0].key_ID`.
+    pub fn json<T: DeserializeOwned>(self) -> Result<T, Error> {
+        serde_path_to_error::deserialize(
+            &mut serde_json::Deserializer::from_slice(&self.body),
+        )
+        .map_err(|e| {
+            let reason = match e.inner().classify() {
+                Category::Data => format!(
+                    "KME returned JSON that is not a valid {}: {}",
+                    type_name::<T>().rsplit("::").next().unwrap_or_default(),
+                    e
+                ),
+                _ => format!("KME returned non-JSON: {}", e.inner()),
+            };
+
+            Error::Protocol {
+                url: self.url.to_string(),
+                reason,
+            }
+        })
+    }
+}
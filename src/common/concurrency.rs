@@ -0,0 +1,56 @@
+// SPDX-FileCopyrightText: © 2023 Merqury Cybersecurity Ltd <info@merqury.eu>
+// SPDX-License-Identifier: PolyForm-Noncommercial-1.0.0
+
+use super::config::CONFIG;
+use futures::{stream, Future, StreamExt};
+use std::time::Instant;
+
+/// Fewest threads the race tests run, as nothing races with a single one.
+pub static MIN_RACE_THREADS: usize = 2;
+
+/// Number of threads the race tests release at once: `CONFIG.max_concurrency`,
+/// but never fewer than [`MIN_RACE_THREADS`].
+pub fn race_threads() -> usize {
+    CONFIG.max_concurrency.get().max(MIN_RACE_THREADS)
+}
+
+/// Largest number of requests in flight at the same moment, given the times
+/// at which each one was sent and its response received.
+pub fn max_in_flight(exchanges: &[(Instant, Instant)]) -> usize {
+    let mut events: Vec<(Instant, bool)> = exchanges
+        .iter()
+        .flat_map(|(sent, received)| [(*sent, true), (*received, false)])
+        .collect();
+    let mut in_flight = 0;
+    let mut max_in_flight = 0;
+
+    // A response received at the very moment another request is sent does
+    // not overlap with it, so ends are counted before starts.
+    events.sort();
+
+    for (_, is_sent) in events {
+        if is_sent {
+            in_flight += 1;
+            max_in_flight = usize::max(max_in_flight, in_flight);
+        } else {
+            in_flight -= 1;
+        }
+    }
+
+    max_in_flight
+}
+
+/// Drives the given futures concurrently, keeping at most
+/// `CONFIG.max_concurrency` of them in flight at any one time.
+///
+/// Each future is polled to completion on its own, so steps that depend on
+/// each other (e.g. requesting a key and then retrieving it by its ID) must be
+/// placed inside the same future to keep their ordering. The outputs are
+/// returned in the same order as the futures were supplied.
+pub async fn run_bounded<I>(tasks: I) -> Vec<<I::Item as Future>::Output>
+where
+    I: IntoIterator,
+    I::Item: Future,
+{
+    stream::iter(tasks).buffered(CONFIG.max_concurrency.get()).collect().await
+}
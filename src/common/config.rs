@@ -0,0 +1,435 @@
+// SPDX-FileCopyrightText: © 2023 Merqury Cybersecurity Ltd <info@merqury.eu>
+// SPDX-License-Identifier: PolyForm-Noncommercial-1.0.0
+
+use super::{
+    error::Error, key_material::Strictness, openapi::Validation,
+    spec::SpecVersion,
+};
+use std::{collections::BTreeMap, env, fmt, num::NonZeroUsize};
+
+static ENV_BASE_URL: &str = "ETSI_014_TEST_SUITE_BASE_URL";
+static ENV_TLS_ROOT_CRT: &str = "ETSI_014_TEST_SUITE_TLS_ROOT_CRT";
+static ENV_SLAVE_BASE_URL: &str = "ETSI_014_TEST_SUITE_SLAVE_BASE_URL";
+static ENV_TLS_SLAVE_ROOT_CRT: &str = "ETSI_014_TEST_SUITE_TLS_SLAVE_ROOT_CRT";
+static ENV_MASTER_SAE_ID: &str = "ETSI_014_TEST_SUITE_MASTER_SAE_ID";
+static ENV_TLS_MASTER_SAE_CERT: &str =
+    "ETSI_014_TEST_SUITE_TLS_MASTER_SAE_CERT";
+static ENV_TLS_SLAVE_SAE_CERT: &str = "ETSI_014_TEST_SUITE_TLS_SLAVE_SAE_CERT";
+static ENV_SLAVE_SAE_ID: &str = "ETSI_014_TEST_SUITE_SLAVE_SAE_ID";
+static ENV_TLS_ADD_SLAVE_SAE_CERT: &str =
+    "ETSI_014_TEST_SUITE_TLS_ADD_SLAVE_SAE_CERT";
+static ENV_ADD_SLAVE_SAE_ID: &str = "ETSI_014_TEST_SUITE_ADD_SLAVE_SAE_ID";
+static ENV_ADD_SLAVE_SAE_IDS: &str = "ETSI_014_TEST_SUITE_ADD_SLAVE_SAE_IDS";
+static ENV_TLS_ADD_SLAVE_SAE_CERTS: &str =
+    "ETSI_014_TEST_SUITE_TLS_ADD_SLAVE_SAE_CERTS";
+static ENV_SAE_PREFIX: &str = "ETSI_014_TEST_SUITE_SAE_";
+static ENV_MASTER_SAE: &str = "ETSI_014_TEST_SUITE_MASTER_SAE";
+static ENV_SLAVE_SAE: &str = "ETSI_014_TEST_SUITE_SLAVE_SAE";
+static ENV_EXTRA_SLAVE_SAES: &str = "ETSI_014_TEST_SUITE_EXTRA_SLAVE_SAES";
+static ENV_OUTSIDER_SAE: &str = "ETSI_014_TEST_SUITE_OUTSIDER_SAE";
+static ENV_MAX_CONCURRENCY: &str = "ETSI_014_TEST_SUITE_MAX_CONCURRENCY";
+static ENV_KEY_SIZE_STRIDE: &str = "ETSI_014_TEST_SUITE_KEY_SIZE_STRIDE";
+static ENV_BASE64_STRICTNESS: &str = "ETSI_014_TEST_SUITE_BASE64_STRICTNESS";
+static ENV_SEED: &str = "ETSI_014_TEST_SUITE_SEED";
+pub static ENV_SPEC_VERSION: &str = "ETSI_014_TEST_SUITE_SPEC_VERSION";
+static ENV_OPENAPI_VALIDATION: &str = "ETSI_014_TEST_SUITE_OPENAPI_VALIDATION";
+static ENV_OPENAPI_REPORT: &str = "ETSI_014_TEST_SUITE_OPENAPI_REPORT";
+static ENV_HAR: &str = "ETSI_014_TEST_SUITE_HAR";
+static ENV_HAR_INCLUDE_KEYS: &str = "ETSI_014_TEST_SUITE_HAR_INCLUDE_KEYS";
+static ENV_REPLAY: &str = "ETSI_014_TEST_SUITE_REPLAY";
+pub static ENV_KEY_REDACTION: &str = "ETSI_014_TEST_SUITE_KEY_REDACTION";
+
+static DEFAULT_MASTER_SAE: &str = "master";
+static DEFAULT_SLAVE_SAE: &str = "slave";
+static DEFAULT_OUTSIDER_SAE: &str = "outsider";
+static LEGACY_ADD_SLAVE_SAE: &str = "add_slave";
+
+static DEFAULT_MAX_CONCURRENCY: usize = 4;
+static DEFAULT_KEY_SIZE_STRIDE: usize = 8;
+
+/// An SAE the tests can act as.
+#[derive(Clone, PartialEq, Eq)]
+pub struct SaeIdentity {
+    pub id: String,
+    /// Path to the certificate, as PEM or DER, or to a PKCS#12 bundle. A PEM
+    /// file or bundle also holds the private key unless `key` is set.
+    pub crt: String,
+    /// Path to the private key, as PEM or DER, if kept apart from the
+    /// certificate.
+    pub key: Option<String>,
+    /// Password of the PKCS#12 bundle or encrypted private key.
+    pub password: Option<String>,
+}
+
+/// Leaves the password out.
+impl fmt::Debug for SaeIdentity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("SaeIdentity")
+            .field("id", &self.id)
+            .field("crt", &self.crt)
+            .field("key", &self.key)
+            .field("password", &self.password.as_ref().map(|_| "<redacted>"))
+            .finish()
+    }
+}
+
+/// The KME serving an SAE, which decides the base URL it is sent to and the
+/// root certificate its KME is trusted with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Side {
+    Master,
+    Slave,
+}
+
+pub struct Config {
+    /// Base URL of the master-side KME, at which keys are requested.
+    pub base_url: String,
+    /// Path to the root certificate of the master-side KME.
+    pub root_crt: String,
+    /// Base URL of the slave-side KME, at which keys are retrieved. Same as
+    /// `base_url` when both SAEs are served by a single KME.
+    pub slave_base_url: String,
+    /// Path to the root certificate of the slave-side KME.
+    pub slave_root_crt: String,
+    /// Every configured SAE identity, by name.
+    pub saes: BTreeMap<String, SaeIdentity>,
+    master_sae: String,
+    slave_sae: String,
+    extra_slave_saes: Vec<String>,
+    outsider_sae: Option<String>,
+    pub max_concurrency: NonZeroUsize,
+    pub key_size_stride: NonZeroUsize,
+    pub base64_strictness: Strictness,
+    pub seed: Option<u64>,
+    pub spec_version: SpecVersion,
+    pub openapi_validation: Validation,
+    pub openapi_report: Option<String>,
+    /// Path of the HAR file to which every exchange is recorded.
+    pub har: Option<String>,
+    /// Whether key material is recorded as is, rather than redacted.
+    pub har_include_keys: bool,
+    /// Path of the HAR file whose responses are replayed instead of sending
+    /// requests to the KME.
+    pub replay: Option<String>,
+}
+
+impl Config {
+    /// Reads the configuration from the environment, panicking with the
+    /// reason if it is incomplete or invalid.
+    pub fn new() -> Self {
+        match Self::load() {
+            Ok(val) => val,
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    /// Reads the configuration from the environment.
+    pub fn load() -> Result<Self, Error> {
+        let saes = Self::extract_identities()?;
+        let master_sae = env::var(ENV_MASTER_SAE)
+            .unwrap_or_else(|_| DEFAULT_MASTER_SAE.to_string());
+        let slave_sae = env::var(ENV_SLAVE_SAE)
+            .unwrap_or_else(|_| DEFAULT_SLAVE_SAE.to_string());
+        let extra_slave_saes: Vec<String> = match env::var(ENV_EXTRA_SLAVE_SAES)
+        {
+            Ok(val) => Self::split_list(&val),
+            Err(_) => saes
+                .keys()
+                .filter(|name| {
+                    ![&master_sae, &slave_sae, DEFAULT_OUTSIDER_SAE]
+                        .contains(&name.as_str())
+                })
+                .cloned()
+                .collect(),
+        };
+        // Without an explicit outsider, the first extra slave is used, as it
+        // is not authorized to retrieve keys unless listed in the request.
+        let outsider_sae = env::var(ENV_OUTSIDER_SAE)
+            .ok()
+            .or_else(|| {
+                saes.contains_key(DEFAULT_OUTSIDER_SAE)
+                    .then(|| DEFAULT_OUTSIDER_SAE.to_string())
+            })
+            .or_else(|| extra_slave_saes.first().cloned());
+
+        for name in [&master_sae, &slave_sae]
+            .into_iter()
+            .chain(&extra_slave_saes)
+            .chain(&outsider_sae)
+        {
+            if !saes.contains_key(name) {
+                return Err(Error::Config(format!(
+                    "SAE '{}' is assigned a role but is not configured. Set \
+                     '{}{}_ID' and '{}{}_CERT'",
+                    name,
+                    ENV_SAE_PREFIX,
+                    name.to_uppercase(),
+                    ENV_SAE_PREFIX,
+                    name.to_uppercase()
+                )));
+            }
+        }
+
+        let base_url = Self::extract_string_value(ENV_BASE_URL)?;
+        let root_crt = Self::extract_string_value(ENV_TLS_ROOT_CRT)?;
+        let key_size_stride: NonZeroUsize = Self::extract_optional_value(
+            ENV_KEY_SIZE_STRIDE,
+            NonZeroUsize::new(DEFAULT_KEY_SIZE_STRIDE).unwrap(),
+        )?;
+
+        // Key sizes are whole numbers of bytes, as the key material is
+        // encoded from bytes.
+        if !key_size_stride.get().is_multiple_of(8) {
+            return Err(Error::Config(format!(
+                "Environment variable '{}' must be a multiple of 8, got {}",
+                ENV_KEY_SIZE_STRIDE, key_size_stride
+            )));
+        }
+
+        Ok(Self {
+            slave_base_url: env::var(ENV_SLAVE_BASE_URL)
+                .unwrap_or_else(|_| base_url.clone()),
+            slave_root_crt: env::var(ENV_TLS_SLAVE_ROOT_CRT)
+                .unwrap_or_else(|_| root_crt.clone()),
+            base_url,
+            root_crt,
+            saes,
+            master_sae,
+            slave_sae,
+            extra_slave_saes,
+            outsider_sae,
+            max_concurrency: Self::extract_optional_value(
+                ENV_MAX_CONCURRENCY,
+                NonZeroUsize::new(DEFAULT_MAX_CONCURRENCY).unwrap(),
+            )?,
+            key_size_stride,
+            base64_strictness: Self::extract_optional_value(
+                ENV_BASE64_STRICTNESS,
+                Strictness::Warn,
+            )?,
+            seed: Self::extract_option(ENV_SEED)?,
+            spec_version: Self::extract_optional_value(
+                ENV_SPEC_VERSION,
+                SpecVersion::default(),
+            )?,
+            openapi_validation: Self::extract_optional_value(
+                ENV_OPENAPI_VALIDATION,
+                Validation::Warn,
+            )?,
+            openapi_report: env::var(ENV_OPENAPI_REPORT).ok(),
+            har: env::var(ENV_HAR).ok(),
+            har_include_keys: Self::extract_optional_value(
+                ENV_HAR_INCLUDE_KEYS,
+                false,
+            )?,
+            replay: env::var(ENV_REPLAY).ok(),
+        })
+    }
+
+    fn extract_string_value(var_name: &str) -> Result<String, Error> {
+        env::var(var_name).map_err(|e| {
+            Error::Config(format!(
+                "Environment variable '{}' not set. Error: {:?}",
+                var_name, e
+            ))
+        })
+    }
+
+    /// Whether the master and slave SAEs are served by different KMEs.
+    pub fn is_multi_kme(&self) -> bool {
+        self.slave_base_url != self.base_url
+    }
+
+    /// The side of the SAE: the master is served by the master-side KME, and
+    /// every other SAE by the slave-side KME.
+    pub fn side(&self, sae: &SaeIdentity) -> Side {
+        match sae == self.master() {
+            true => Side::Master,
+            false => Side::Slave,
+        }
+    }
+
+    /// Base URL of the KME of the given side.
+    pub fn base_url_of(&self, side: Side) -> &String {
+        match side {
+            Side::Master => &self.base_url,
+            Side::Slave => &self.slave_base_url,
+        }
+    }
+
+    /// Path to the root certificate of the KME of the given side.
+    pub fn root_crt_of(&self, side: Side) -> &String {
+        match side {
+            Side::Master => &self.root_crt,
+            Side::Slave => &self.slave_root_crt,
+        }
+    }
+
+    /// The SAE acting as master, which requests keys.
+    pub fn master(&self) -> &SaeIdentity {
+        &self.saes[&self.master_sae]
+    }
+
+    /// The SAE acting as slave, which retrieves the keys by their IDs.
+    pub fn slave(&self) -> &SaeIdentity {
+        &self.saes[&self.slave_sae]
+    }
+
+    /// The SAEs that can be listed as additional slaves.
+    pub fn extra_slaves(&self) -> Vec<&SaeIdentity> {
+        self.extra_slave_saes.iter().map(|name| &self.saes[name]).collect()
+    }
+
+    /// An SAE that is not authorized to retrieve the keys requested by the
+    /// master for the slave.
+    pub fn outsider(&self) -> &SaeIdentity {
+        match self.try_outsider() {
+            Some(val) => val,
+            None => panic!(
+                "No SAE configured for the outsider role. Set '{}'",
+                ENV_OUTSIDER_SAE
+            ),
+        }
+    }
+
+    /// The outsider SAE, if one is configured.
+    pub fn try_outsider(&self) -> Option<&SaeIdentity> {
+        self.outsider_sae.as_ref().map(|name| &self.saes[name])
+    }
+
+    /// Collects the SAE identities named by the
+    /// `ETSI_014_TEST_SUITE_SAE_<NAME>_ID` variables, with their
+    /// `_CERT` and optional `_KEY` and `_PASSWORD` counterparts. Names are
+    /// lower case.
+    ///
+    /// The legacy master, slave and additional slave variables are still
+    /// accepted, and define the `master`, `slave` and `add_slave` (followed
+    /// by `add_slave_2`, `add_slave_3`...) identities respectively.
+    fn extract_identities() -> Result<BTreeMap<String, SaeIdentity>, Error> {
+        let mut saes = BTreeMap::new();
+        let legacy = [
+            (
+                DEFAULT_MASTER_SAE,
+                ENV_MASTER_SAE_ID,
+                ENV_TLS_MASTER_SAE_CERT,
+            ),
+            (DEFAULT_SLAVE_SAE, ENV_SLAVE_SAE_ID, ENV_TLS_SLAVE_SAE_CERT),
+            (
+                LEGACY_ADD_SLAVE_SAE,
+                ENV_ADD_SLAVE_SAE_ID,
+                ENV_TLS_ADD_SLAVE_SAE_CERT,
+            ),
+        ];
+
+        for (name, id_var_name, crt_var_name) in legacy {
+            if let Ok(id) = env::var(id_var_name) {
+                saes.insert(
+                    name.to_string(),
+                    SaeIdentity {
+                        id,
+                        crt: Self::extract_string_value(crt_var_name)?,
+                        key: None,
+                        password: None,
+                    },
+                );
+            }
+        }
+
+        let ids = Self::split_list(
+            &env::var(ENV_ADD_SLAVE_SAE_IDS).unwrap_or_default(),
+        );
+        let crts = Self::split_list(
+            &env::var(ENV_TLS_ADD_SLAVE_SAE_CERTS).unwrap_or_default(),
+        );
+
+        if ids.len() != crts.len() {
+            return Err(Error::Config(format!(
+                "Environment variables '{}' and '{}' must list the same \
+                 number of entries",
+                ENV_ADD_SLAVE_SAE_IDS, ENV_TLS_ADD_SLAVE_SAE_CERTS
+            )));
+        }
+
+        for (index, (id, crt)) in ids.into_iter().zip(crts).enumerate() {
+            saes.insert(
+                format!("{}_{}", LEGACY_ADD_SLAVE_SAE, index + 2),
+                SaeIdentity {
+                    id,
+                    crt,
+                    key: None,
+                    password: None,
+                },
+            );
+        }
+
+        for (var_name, id) in env::vars() {
+            let name = match var_name
+                .strip_prefix(ENV_SAE_PREFIX)
+                .and_then(|val| val.strip_suffix("_ID"))
+            {
+                Some(val) if !val.is_empty() => val,
+                _ => continue,
+            };
+            let prefix = format!("{}{}", ENV_SAE_PREFIX, name);
+
+            saes.insert(
+                name.to_lowercase(),
+                SaeIdentity {
+                    id,
+                    crt: Self::extract_string_value(&format!(
+                        "{}_CERT",
+                        prefix
+                    ))?,
+                    key: env::var(format!("{}_KEY", prefix)).ok(),
+                    password: env::var(format!("{}_PASSWORD", prefix)).ok(),
+                },
+            );
+        }
+
+        Ok(saes)
+    }
+
+    fn split_list(list: &str) -> Vec<String> {
+        list.split(',')
+            .map(str::trim)
+            .filter(|val| !val.is_empty())
+            .map(String::from)
+            .collect()
+    }
+
+    fn extract_optional_value<T>(var_name: &str, default: T) -> Result<T, Error>
+    where
+        T: std::str::FromStr,
+        T::Err: std::fmt::Debug,
+    {
+        Ok(Self::extract_option(var_name)?.unwrap_or(default))
+    }
+
+    fn extract_option<T>(var_name: &str) -> Result<Option<T>, Error>
+    where
+        T: std::str::FromStr,
+        T::Err: std::fmt::Debug,
+    {
+        match env::var(var_name) {
+            Ok(val) => match val.parse::<T>() {
+                Ok(parsed_val) => Ok(Some(parsed_val)),
+                Err(e) => Err(Error::Config(format!(
+                    "Environment variable '{}' has an invalid value '{}'. \
+                     Error: {:?}",
+                    var_name, val, e
+                ))),
+            },
+            Err(_) => Ok(None),
+        }
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+lazy_static! {
+    pub static ref CONFIG: Config = Config::new();
+}
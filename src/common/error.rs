@@ -0,0 +1,170 @@
+// SPDX-FileCopyrightText: © 2023 Merqury Cybersecurity Ltd <info@merqury.eu>
+// SPDX-License-Identifier: PolyForm-Noncommercial-1.0.0
+
+//! Errors of the test suite itself, as opposed to the findings of the checks,
+//! classified by where things went wrong: in the configuration, in the
+//! certificates, on the way to the KME, in the TLS handshake, in what the
+//! KME replied, or in replaying a recorded run.
+
+use std::{error, fmt, io};
+
+#[derive(Clone, PartialEq, Eq)]
+pub enum Error {
+    /// An environment variable is missing or invalid.
+    Config(String),
+    /// A certificate or private key cannot be read or parsed.
+    Certificate { path: String, reason: String },
+    /// The KME cannot be reached, or the connection failed mid-exchange.
+    Transport {
+        url: String,
+        reason: String,
+        /// Whether the connection was established before failing, e.g. when
+        /// the KME closes it while the request is being sent.
+        connected: bool,
+    },
+    /// The TLS handshake with the KME failed.
+    Tls { url: String, reason: String },
+    /// The KME replied, but not as the specification requires.
+    Protocol { url: String, reason: String },
+    /// The recorded run being replayed has no exchange for a request, or an
+    /// invalid one.
+    Replay { url: String, reason: String },
+}
+
+impl Error {
+    /// Short name of the class of the error, as recorded in HAR files.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Error::Config(_) => "config",
+            Error::Certificate { .. } => "certificate",
+            Error::Transport { .. } => "transport",
+            Error::Tls { .. } => "tls",
+            Error::Protocol { .. } => "protocol",
+            Error::Replay { .. } => "replay",
+        }
+    }
+
+    /// The error without its class and location, such as
+    /// `connection refused`.
+    pub fn reason(&self) -> &str {
+        match self {
+            Error::Config(reason)
+            | Error::Certificate { reason, .. }
+            | Error::Transport { reason, .. }
+            | Error::Tls { reason, .. }
+            | Error::Protocol { reason, .. }
+            | Error::Replay { reason, .. } => reason,
+        }
+    }
+
+    /// Classifies an error of `reqwest`, which does not tell TLS failures
+    /// apart from other connection failures. The underlying I/O error is
+    /// looked at instead: `rustls` reports its errors as invalid data.
+    pub fn from_reqwest(e: &reqwest::Error) -> Self {
+        let url = e
+            .url()
+            .map(|val| val.to_string())
+            .unwrap_or_else(|| "<unknown URL>".to_string());
+
+        if e.is_builder() {
+            return Error::Config(format!("invalid request to {}: {}", url, e));
+        }
+
+        let mut sources = Vec::new();
+        let mut source = error::Error::source(e);
+
+        while let Some(val) = source {
+            sources.push(val);
+            source = val.source();
+        }
+
+        let innermost = sources.last().copied();
+        // I/O errors wrapping other I/O errors skip them as sources, so they
+        // are unwrapped separately.
+        let io_kind = sources
+            .iter()
+            .rev()
+            .find_map(|val| val.downcast_ref::<io::Error>())
+            .map(|mut val| {
+                while let Some(inner) =
+                    val.get_ref().and_then(|e| e.downcast_ref::<io::Error>())
+                {
+                    val = inner;
+                }
+
+                val.kind()
+            });
+        let reason = match (e.is_timeout(), io_kind) {
+            (true, _) | (_, Some(io::ErrorKind::TimedOut)) => {
+                "timed out".to_string()
+            }
+            (_, Some(io::ErrorKind::ConnectionRefused)) => {
+                "connection refused".to_string()
+            }
+            (_, Some(io::ErrorKind::ConnectionReset)) => {
+                "connection reset".to_string()
+            }
+            (_, Some(io::ErrorKind::BrokenPipe)) => {
+                "connection closed by the KME".to_string()
+            }
+            _ => innermost
+                .map(|val| val.to_string())
+                .unwrap_or_else(|| e.to_string()),
+        };
+
+        if io_kind == Some(io::ErrorKind::InvalidData) {
+            return Error::Tls { url, reason };
+        }
+
+        if e.is_decode() || e.is_redirect() || e.is_status() {
+            return Error::Protocol { url, reason };
+        }
+
+        Error::Transport {
+            url,
+            reason,
+            connected: !e.is_connect() && !e.is_timeout(),
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Config(reason) => {
+                write!(f, "configuration error: {}", reason)
+            }
+            Error::Certificate { path, reason } => {
+                write!(f, "certificate error: '{}': {}", path, reason)
+            }
+            Error::Transport { url, reason, .. } => {
+                write!(f, "transport error: {} at {}", reason, url)
+            }
+            Error::Tls { url, reason } => {
+                write!(f, "TLS error: {} at {}", reason, url)
+            }
+            Error::Protocol { url, reason } => {
+                write!(f, "protocol error: {} at {}", reason, url)
+            }
+            Error::Replay { url, reason } => {
+                write!(f, "replay error: {} at {}", reason, url)
+            }
+        }
+    }
+}
+
+/// Same as `Display`, so that a failed `unwrap()` reports the error rather
+/// than its structure.
+impl fmt::Debug for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+impl error::Error for Error {}
+
+impl From<reqwest::Error> for Error {
+    fn from(e: reqwest::Error) -> Self {
+        Self::from_reqwest(&e)
+    }
+}
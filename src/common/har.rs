@@ -0,0 +1,335 @@
+// SPDX-FileCopyrightText: © 2023 Merqury Cybersecurity Ltd <info@merqury.eu>
+// SPDX-License-Identifier: PolyForm-Noncommercial-1.0.0
+
+//! Recording of the HTTP exchanges made through [`super::client::Client`] to
+//! an HTTP Archive (HAR 1.2) file, which can be imported by most HTTP tooling.
+//! Key material is redacted from the recorded bodies, following the global
+//! redaction policy, unless explicitly included.
+
+use super::{
+    config::CONFIG,
+    error::Error,
+    redaction::{Redaction, REDACTION},
+};
+use reqwest::{header::HeaderMap, Method, StatusCode, Url, Version};
+use serde_json::{json, Value};
+use std::{
+    fs::OpenOptions,
+    io::{self, Read, Seek, SeekFrom, Write},
+    sync::Mutex,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+static TRAILER: &str = "\n]}}\n";
+
+lazy_static! {
+    /// Serializes the updates of the HAR file by the threads of a test run.
+    static ref LOCK: Mutex<()> = Mutex::new(());
+}
+
+/// A request as it was sent.
+pub struct Request<'a> {
+    pub method: &'a Method,
+    pub url: &'a Url,
+    pub headers: &'a HeaderMap,
+    pub body: Option<&'a [u8]>,
+}
+
+/// The outcome of a request: either a response, or the error that prevented
+/// one from being received.
+pub enum Outcome<'a> {
+    Response {
+        status: StatusCode,
+        version: Version,
+        headers: &'a HeaderMap,
+        body: &'a [u8],
+        /// Time from sending the request to receiving the response headers.
+        wait: Duration,
+        /// Time spent reading the response body.
+        receive: Duration,
+    },
+    Error(&'a Error),
+}
+
+/// Details of the TLS connection, as configured on the client.
+#[derive(Debug, Clone)]
+pub struct Tls {
+    pub sae_id: String,
+    pub client_certificate: String,
+    pub root_certificates: Vec<String>,
+}
+
+/// Builds the entry of the exchange, if recording is enabled.
+pub fn recorded_entry(
+    started: SystemTime,
+    request: Request,
+    outcome: Outcome,
+    tls: &Tls,
+) -> Option<Value> {
+    CONFIG.har.as_ref()?;
+
+    let redaction = match CONFIG.har_include_keys {
+        true => Redaction::Off,
+        false => *REDACTION,
+    };
+
+    Some(entry(started, request, outcome, tls, redaction))
+}
+
+/// Appends the entry to the HAR file. Only called when recording is enabled.
+pub fn record(entry: &Value) {
+    let path = CONFIG.har.as_ref().unwrap();
+    let _guard = LOCK.lock().unwrap_or_else(|e| e.into_inner());
+
+    if let Err(e) = append(path, entry) {
+        panic!("Failed to record to '{}'. Error: {:?}", path, e);
+    }
+}
+
+/// Same as [`record`], but the file is written on a thread dedicated to
+/// blocking operations, so that the asynchronous workers are not held up by
+/// the file system or by the lock.
+pub async fn record_async(entry: Value) {
+    let written = tokio::task::spawn_blocking(move || record(&entry)).await;
+
+    if let Err(e) = written {
+        std::panic::resume_unwind(e.into_panic());
+    }
+}
+
+/// Appends the entry to the log of the file, which is created if needed, so
+/// that a single archive covers every test binary of a run. The file always
+/// ends with `TRAILER`, which is overwritten by the next entry, so that the
+/// archive does not have to be rewritten as it grows.
+fn append(path: &str, entry: &Value) -> io::Result<()> {
+    let mut file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(path)?;
+    let len = file.metadata()?.len();
+
+    if len == 0 {
+        let creator = json!({
+            "name": env!("CARGO_PKG_NAME"),
+            "version": env!("CARGO_PKG_VERSION"),
+        });
+
+        let comment = json!(format!("ETSI GS QKD 014 {}", CONFIG.spec_version));
+
+        write!(
+            file,
+            "{{\"log\":{{\"version\":\"1.2\",\"creator\":{},\
+             \"comment\":{},\"entries\":[\n{}{}",
+            creator, comment, entry, TRAILER
+        )
+    } else {
+        let mut trailer = [0; TRAILER.len()];
+
+        file.seek(SeekFrom::End(-(TRAILER.len() as i64)))?;
+        file.read_exact(&mut trailer)?;
+
+        if trailer != TRAILER.as_bytes() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "not a HAR file recorded by the test suite",
+            ));
+        }
+
+        file.seek(SeekFrom::End(-(TRAILER.len() as i64)))?;
+        write!(file, ",\n{}{}", entry, TRAILER)
+    }
+}
+
+/// Builds the HAR entry of an exchange.
+pub fn entry(
+    started: SystemTime,
+    request: Request,
+    outcome: Outcome,
+    tls: &Tls,
+    redaction: Redaction,
+) -> Value {
+    let request_body = request.body.unwrap_or_default();
+    let mut har_request = json!({
+        "method": request.method.as_str(),
+        "url": request.url.as_str(),
+        "httpVersion": "HTTP/1.1",
+        "cookies": [],
+        "headers": headers(request.headers),
+        "queryString": request
+            .url
+            .query_pairs()
+            .map(|(name, value)| json!({"name": name, "value": value}))
+            .collect::<Vec<Value>>(),
+        "headersSize": -1,
+        "bodySize": request_body.len(),
+    });
+
+    if request.body.is_some() {
+        har_request["postData"] = json!({
+            "mimeType": content_type(request.headers),
+            "text": body_text(request_body, redaction),
+        });
+    }
+
+    let (har_response, wait, receive, error) = match outcome {
+        Outcome::Response {
+            status,
+            version,
+            headers: response_headers,
+            body,
+            wait,
+            receive,
+        } => {
+            har_request["httpVersion"] = json!(format!("{:?}", version));
+
+            (
+                json!({
+                    "status": status.as_u16(),
+                    "statusText": status.canonical_reason().unwrap_or_default(),
+                    "httpVersion": format!("{:?}", version),
+                    "cookies": [],
+                    "headers": headers(response_headers),
+                    "content": {
+                        "size": body.len(),
+                        "mimeType": content_type(response_headers),
+                        "text": body_text(body, redaction),
+                    },
+                    "redirectURL": "",
+                    "headersSize": -1,
+                    "bodySize": body.len(),
+                }),
+                wait,
+                receive,
+                None,
+            )
+        }
+        // HAR records failed requests as responses with a status of 0.
+        Outcome::Error(e) => (
+            json!({
+                "status": 0,
+                "statusText": "",
+                "httpVersion": "",
+                "cookies": [],
+                "headers": [],
+                "content": {"size": 0, "mimeType": ""},
+                "redirectURL": "",
+                "headersSize": -1,
+                "bodySize": -1,
+            }),
+            Duration::ZERO,
+            Duration::ZERO,
+            Some(json!({
+                "message": e.to_string(),
+                "kind": e.kind(),
+                "reason": e.reason(),
+                "connected": matches!(
+                    e,
+                    Error::Transport {
+                        connected: true,
+                        ..
+                    }
+                ),
+            })),
+        ),
+    };
+    let mut entry = json!({
+        "startedDateTime": timestamp(started),
+        "time": milliseconds(wait + receive),
+        "request": har_request,
+        "response": har_response,
+        "cache": {},
+        "timings": {
+            "send": 0,
+            "wait": milliseconds(wait),
+            "receive": milliseconds(receive),
+        },
+        "_check": super::check_name(),
+        "_tls": {
+            "minVersion": "TLSv1.3",
+            "saeId": tls.sae_id,
+            "clientCertificate": tls.client_certificate,
+            "rootCertificates": tls.root_certificates,
+        },
+    });
+
+    if let Some(error) = error {
+        entry["_error"] = error;
+    }
+
+    entry
+}
+
+/// Returns the body as text, with the key material redacted.
+fn body_text(body: &[u8], redaction: Redaction) -> String {
+    if redaction != Redaction::Off {
+        if let Ok(mut val) = serde_json::from_slice::<Value>(body) {
+            if redaction.apply_to_body(&mut val) {
+                return val.to_string();
+            }
+        }
+    }
+
+    String::from_utf8_lossy(body).into_owned()
+}
+
+fn headers(headers: &HeaderMap) -> Vec<Value> {
+    headers
+        .iter()
+        .map(|(name, value)| {
+            json!({
+                "name": name.as_str(),
+                "value": String::from_utf8_lossy(value.as_bytes()),
+            })
+        })
+        .collect()
+}
+
+fn content_type(headers: &HeaderMap) -> String {
+    headers
+        .get(reqwest::header::CONTENT_TYPE)
+        .map(|val| String::from_utf8_lossy(val.as_bytes()).into_owned())
+        .unwrap_or_default()
+}
+
+fn milliseconds(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+/// Formats the time in ISO 8601, as required by HAR, e.g.
+/// `2023-01-31T12:34:56.789Z`.
+fn timestamp(time: SystemTime) -> String {
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let secs = since_epoch.as_secs();
+    let (hour, min, sec) = (secs / 3600 % 24, secs / 60 % 60, secs % 60);
+    // Conversion of the days since the epoch to a civil date, from
+    // http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let days = (secs / 86400) as i64 + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524
+        - day_of_era / 146_096)
+        / 365;
+    let day_of_year =
+        day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+        year,
+        month,
+        day,
+        hour,
+        min,
+        sec,
+        since_epoch.subsec_millis()
+    )
+}
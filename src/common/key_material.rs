@@ -0,0 +1,134 @@
+// SPDX-FileCopyrightText: © 2023 Merqury Cybersecurity Ltd <info@merqury.eu>
+// SPDX-License-Identifier: PolyForm-Noncommercial-1.0.0
+
+//! Decoding of the base64 key material returned by the KME. Decoding is
+//! lenient, so that keys can be compared byte by byte regardless of how they
+//! were encoded, while the encoding used is reported separately. Decoded keys
+//! are zeroed when dropped.
+
+use base64::{
+    alphabet,
+    engine::{DecodePaddingMode, GeneralPurpose, GeneralPurposeConfig},
+    Engine,
+};
+use std::{fmt, str::FromStr};
+use zeroize::Zeroizing;
+
+/// What to do when key material is not in the canonical encoding.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Strictness {
+    /// Non-canonical encodings are printed as warnings.
+    Warn,
+    /// Non-canonical encodings fail the check.
+    Fail,
+}
+
+impl FromStr for Strictness {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "warn" => Ok(Self::Warn),
+            "fail" => Ok(Self::Fail),
+            _ => Err("expected one of 'warn' or 'fail'".to_string()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Alphabet {
+    /// `+` and `/`, as defined in section 4 of RFC 4648.
+    Standard,
+    /// `-` and `_`, as defined in section 5 of RFC 4648.
+    UrlSafe,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Encoding {
+    pub alphabet: Alphabet,
+    /// Whether the encoded value is padded with `=` to a multiple of 4
+    /// characters.
+    pub padded: bool,
+    /// Whether the encoded value contains whitespace other than line breaks,
+    /// such as spaces, tabs or form feeds.
+    pub whitespace: bool,
+    /// Whether the encoded value contains line breaks, as produced by MIME
+    /// encoders.
+    pub line_breaks: bool,
+}
+
+impl Encoding {
+    /// Whether this is the standard, padded, encoding the KME is expected to
+    /// use.
+    pub fn is_canonical(&self) -> bool {
+        self.alphabet == Alphabet::Standard
+            && self.padded
+            && !self.whitespace
+            && !self.line_breaks
+    }
+}
+
+impl fmt::Display for Encoding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let alphabet = match self.alphabet {
+            Alphabet::Standard => "standard",
+            Alphabet::UrlSafe => "URL-safe",
+        };
+        let padding = if self.padded { "padded" } else { "unpadded" };
+
+        write!(f, "{}, {}", alphabet, padding)?;
+
+        if self.whitespace {
+            write!(f, ", with whitespace")?;
+        }
+
+        if self.line_breaks {
+            write!(f, ", with line breaks")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Decodes key material, accepting either alphabet, with or without padding
+/// and ignoring whitespace and line breaks, and returns the decoded bytes
+/// together with the encoding that was used.
+pub fn decode(encoded: &str) -> Result<(Zeroizing<Vec<u8>>, Encoding), String> {
+    let whitespace = encoded
+        .chars()
+        .any(|val| val.is_ascii_whitespace() && !matches!(val, '\r' | '\n'));
+    let line_breaks = encoded.contains(['\r', '\n']);
+    let encoded: Zeroizing<String> = Zeroizing::new(
+        encoded.chars().filter(|val| !val.is_ascii_whitespace()).collect(),
+    );
+    let has_standard = encoded.contains(['+', '/']);
+    let has_url_safe = encoded.contains(['-', '_']);
+    let alphabet = match (has_standard, has_url_safe) {
+        (true, true) => {
+            return Err("mixes the standard and URL-safe alphabets".to_string())
+        }
+        (false, true) => Alphabet::UrlSafe,
+        _ => Alphabet::Standard,
+    };
+    let engine = GeneralPurpose::new(
+        match alphabet {
+            Alphabet::Standard => &alphabet::STANDARD,
+            Alphabet::UrlSafe => &alphabet::URL_SAFE,
+        },
+        GeneralPurposeConfig::new()
+            .with_decode_padding_mode(DecodePaddingMode::Indifferent),
+    );
+    let decoded = Zeroizing::new(
+        engine.decode(encoded.as_bytes()).map_err(|e| e.to_string())?,
+    );
+
+    Ok((
+        decoded,
+        Encoding {
+            alphabet,
+            padded: encoded.len().is_multiple_of(4),
+            whitespace,
+            line_breaks,
+        },
+    ))
+}
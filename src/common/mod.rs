@@ -0,0 +1,155 @@
+// SPDX-FileCopyrightText: © 2023 Merqury Cybersecurity Ltd <info@merqury.eu>
+// SPDX-License-Identifier: PolyForm-Noncommercial-1.0.0
+
+pub mod capabilities;
+pub mod certificate;
+pub mod client;
+pub mod concurrency;
+pub mod config;
+pub mod error;
+pub mod har;
+pub mod key_material;
+pub mod openapi;
+pub mod redaction;
+pub mod replay;
+pub mod spec;
+pub mod status_rules;
+
+use config::{SaeIdentity, Side, CONFIG};
+use error::Error;
+use std::fs;
+
+/// Applies the TLS settings shared by the blocking and asynchronous clients to
+/// a client builder. Their builders have no common trait, hence the macro.
+macro_rules! configure_tls {
+    ($builder:expr, $sae:expr, $side:expr) => {{
+        let (root_certificate, identity) = load_tls($sae, $side)?;
+
+        $builder
+            .add_root_certificate(root_certificate)
+            .identity(identity)
+            .min_tls_version(reqwest::tls::Version::TLS_1_3)
+            .danger_accept_invalid_certs(false)
+            .use_rustls_tls()
+    }};
+}
+
+/// Builds the client of an SAE, which trusts only the root certificate of the
+/// KME of the given side.
+pub fn build_client(
+    sae: &SaeIdentity,
+    side: Side,
+) -> Result<client::Client, Error> {
+    let tls = har_tls(sae, side);
+
+    // Replayed requests are never sent, so the certificates are not needed.
+    if CONFIG.replay.is_some() {
+        return Ok(client::Client::new(reqwest::blocking::Client::new(), tls));
+    }
+
+    let client =
+        configure_tls!(reqwest::blocking::Client::builder(), sae, side)
+            .build()
+            .map_err(|e| client_error(sae, e))?;
+
+    Ok(client::Client::new(client, tls))
+}
+
+/// Same as [`build_client`], for the workloads that keep several requests in
+/// flight. Its exchanges are validated, recorded and replayed alike.
+pub fn build_async_client(
+    sae: &SaeIdentity,
+    side: Side,
+) -> Result<client::AsyncClient, Error> {
+    let tls = har_tls(sae, side);
+
+    if CONFIG.replay.is_some() {
+        return Ok(client::AsyncClient::new(reqwest::Client::new(), tls));
+    }
+
+    let client = configure_tls!(reqwest::Client::builder(), sae, side)
+        .build()
+        .map_err(|e| client_error(sae, e))?;
+
+    Ok(client::AsyncClient::new(client, tls))
+}
+
+/// The TLS settings of a client, as recorded.
+fn har_tls(sae: &SaeIdentity, side: Side) -> har::Tls {
+    har::Tls {
+        sae_id: sae.id.clone(),
+        client_certificate: sae.crt.clone(),
+        root_certificates: vec![CONFIG.root_crt_of(side).clone()],
+    }
+}
+
+/// Loads the root certificate of the KME of the given side, and the identity
+/// of the SAE.
+fn load_tls(
+    sae: &SaeIdentity,
+    side: Side,
+) -> Result<(reqwest::Certificate, reqwest::Identity), Error> {
+    Ok((
+        load_root_certificate(CONFIG.root_crt_of(side))?,
+        load_identity(sae)?,
+    ))
+}
+
+/// Name of the check being run. `cargo test` names the thread of each test
+/// after it, and threads spawned by a check are expected to be named the same.
+pub fn check_name() -> String {
+    std::thread::current().name().unwrap_or("<unnamed>").to_owned()
+}
+
+pub fn load_root_certificate(
+    path: &str,
+) -> Result<reqwest::Certificate, Error> {
+    reqwest::Certificate::from_pem(&read_pem(path)?).map_err(|e| {
+        Error::Certificate {
+            path: path.to_string(),
+            reason: format!("not a valid PEM certificate: {}", e),
+        }
+    })
+}
+
+/// The root certificates of the master-side and slave-side KMEs, once each.
+pub fn root_certificate_paths() -> Vec<&'static String> {
+    let mut paths = vec![&CONFIG.root_crt];
+
+    if CONFIG.slave_root_crt != CONFIG.root_crt {
+        paths.push(&CONFIG.slave_root_crt);
+    }
+
+    paths
+}
+
+/// Loads the certificate and private key of an SAE, in any of the formats
+/// supported by [`certificate::Identity`].
+pub fn load_identity(sae: &SaeIdentity) -> Result<reqwest::Identity, Error> {
+    let path = sae.key.as_ref().unwrap_or(&sae.crt);
+    let identity_error = |reason: String| Error::Certificate {
+        path: path.clone(),
+        reason: format!(
+            "not a valid certificate and private key for SAE '{}': {}",
+            sae.id, reason
+        ),
+    };
+    let pem =
+        certificate::Identity::read(sae)?.to_pem().map_err(identity_error)?;
+
+    reqwest::Identity::from_pem(&pem).map_err(|e| identity_error(e.to_string()))
+}
+
+fn read_pem(path: &str) -> Result<Vec<u8>, Error> {
+    fs::read(path).map_err(|e| Error::Certificate {
+        path: path.to_string(),
+        reason: format!("cannot be read: {}", e),
+    })
+}
+
+fn client_error(sae: &SaeIdentity, e: reqwest::Error) -> Error {
+    Error::Config(format!(
+        "cannot build the client of SAE '{}': {}",
+        sae.id, e
+    ))
+}
@@ -0,0 +1,368 @@
+// SPDX-FileCopyrightText: © 2023 Merqury Cybersecurity Ltd <info@merqury.eu>
+// SPDX-License-Identifier: PolyForm-Noncommercial-1.0.0
+
+//! Validation of the KME responses against the OpenAPI description of the
+//! selected version of ETSI GS QKD 014 bundled with the test suite, which
+//! gives an opinion on conformance that is independent of the assertions made
+//! by the tests themselves.
+
+use super::{config::CONFIG, error::Error, redaction, spec::SpecVersion};
+use base64::Engine;
+use reqwest::{Method, StatusCode, Url};
+use serde_json::{json, Value};
+use std::{
+    collections::HashMap,
+    fs::OpenOptions,
+    io::{self, Write},
+    str::FromStr,
+};
+use uuid::Uuid;
+
+lazy_static! {
+    static ref SPECS: HashMap<SpecVersion, Value> = SpecVersion::ALL
+        .iter()
+        .map(|version| {
+            match serde_yaml::from_str(version.openapi_document()) {
+                Ok(val) => (*version, val),
+                Err(e) => panic!(
+                    "Invalid bundled OpenAPI document for version {}. \
+                     Error: {:?}",
+                    version, e
+                ),
+            }
+        })
+        .collect();
+}
+
+/// What to do when a response does not conform to the OpenAPI document.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Validation {
+    /// Responses are not validated.
+    Off,
+    /// Violations are printed and, if configured, written to the report, but
+    /// do not fail the check.
+    Warn,
+    /// Violations fail the check that received the response.
+    Fail,
+}
+
+impl FromStr for Validation {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "off" => Ok(Self::Off),
+            "warn" => Ok(Self::Warn),
+            "fail" => Ok(Self::Fail),
+            _ => Err("expected one of 'off', 'warn' or 'fail'".to_string()),
+        }
+    }
+}
+
+/// Validates a response received from the KME and reports any violation
+/// according to `CONFIG.openapi_validation`, failing with a protocol error if
+/// violations must fail the check.
+pub fn check_response(
+    method: &Method,
+    url: &Url,
+    status: StatusCode,
+    content_type: Option<&str>,
+    body: &[u8],
+) -> Result<(), Error> {
+    if CONFIG.openapi_validation == Validation::Off {
+        return Ok(());
+    }
+
+    let violations = validate_response(
+        CONFIG.spec_version,
+        method,
+        url,
+        status,
+        content_type,
+        body,
+    );
+
+    if violations.is_empty() {
+        return Ok(());
+    }
+
+    let check = super::check_name();
+
+    for violation in &violations {
+        eprintln!(
+            "OpenAPI violation in '{}': {} {} returned {}: {}",
+            check, method, url, status, violation
+        );
+    }
+
+    if let Some(path) = &CONFIG.openapi_report {
+        let line = json!({
+            "check": check,
+            "spec_version": CONFIG.spec_version,
+            "method": method.as_str(),
+            "url": url.as_str(),
+            "status": status.as_u16(),
+            "violations": violations,
+        });
+
+        append_report(path, &line).map_err(|e| {
+            Error::Config(format!(
+                "cannot write the OpenAPI report '{}': {}",
+                path, e
+            ))
+        })?;
+    }
+
+    match CONFIG.openapi_validation {
+        Validation::Fail => Err(Error::Protocol {
+            url: url.to_string(),
+            reason: format!(
+                "response to {} does not conform to the OpenAPI document: {}",
+                method,
+                violations.join("; ")
+            ),
+        }),
+        _ => Ok(()),
+    }
+}
+
+fn append_report(path: &str, line: &Value) -> io::Result<()> {
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+
+    // A single write per line keeps the lines of concurrent checks apart.
+    file.write_all(format!("{}\n", line).as_bytes())
+}
+
+/// Returns a description of every way in which the response deviates from
+/// the operation it answers. Requests that do not correspond to an operation
+/// of the document, e.g. unsupported methods, are not validated.
+pub fn validate_response(
+    spec_version: SpecVersion,
+    method: &Method,
+    url: &Url,
+    status: StatusCode,
+    content_type: Option<&str>,
+    body: &[u8],
+) -> Vec<String> {
+    let spec = &SPECS[&spec_version];
+    let (path, operation) = match find_operation(spec, method, url) {
+        Some(val) => val,
+        None => return Vec::new(),
+    };
+    let responses = &operation["responses"];
+    let response = match responses
+        .get(status.as_str())
+        .or_else(|| responses.get("default"))
+    {
+        Some(val) => resolve(spec, val),
+        None => {
+            let allowed: Vec<&str> = responses
+                .as_object()
+                .map(|val| val.keys().map(String::as_str).collect())
+                .unwrap_or_default();
+
+            return vec![format!(
+                "status {} is not defined for {} {}, expected one of: {}",
+                status.as_u16(),
+                method,
+                path,
+                allowed.join(", ")
+            )];
+        }
+    };
+    let content = match response["content"].as_object() {
+        Some(val) => val,
+        None => return Vec::new(),
+    };
+    let media_type = content_type
+        .and_then(|val| val.split(';').next())
+        .map(|val| val.trim().to_ascii_lowercase());
+    let schema = match media_type.as_deref().and_then(|val| content.get(val)) {
+        Some(val) => &val["schema"],
+        None => {
+            let allowed: Vec<&str> =
+                content.keys().map(String::as_str).collect();
+
+            return vec![format!(
+                "content type {} is not defined for status {}, expected one \
+                 of: {}",
+                content_type
+                    .map_or("<none>".to_string(), |val| format!("'{}'", val)),
+                status.as_u16(),
+                allowed.join(", ")
+            )];
+        }
+    };
+    let value = match serde_json::from_slice::<Value>(body) {
+        Ok(val) => val,
+        Err(e) => return vec![format!("body is not valid JSON: {}", e)],
+    };
+    let mut violations = Vec::new();
+
+    validate_schema(spec, schema, &value, "", &mut violations);
+
+    violations
+}
+
+/// Returns the path template and the operation matching the request. The
+/// templates are matched against the end of the URL path, so that the KME
+/// may be served under any prefix.
+fn find_operation(
+    spec: &'static Value,
+    method: &Method,
+    url: &Url,
+) -> Option<(&'static str, &'static Value)> {
+    let segments: Vec<&str> = url.path_segments()?.collect();
+
+    spec["paths"].as_object()?.iter().find_map(|(path, item)| {
+        let template: Vec<&str> =
+            path.trim_start_matches('/').split('/').collect();
+        let template = &template[template.len().saturating_sub(2)..];
+
+        if segments.len() < template.len() {
+            return None;
+        }
+
+        let matches = template
+            .iter()
+            .zip(&segments[segments.len() - template.len()..])
+            .all(|(expected, actual)| {
+                expected.starts_with('{') || expected == actual
+            });
+
+        match item.get(method.as_str().to_ascii_lowercase()) {
+            Some(operation) if matches => Some((path.as_str(), operation)),
+            _ => None,
+        }
+    })
+}
+
+/// Follows a local `$ref`, if any.
+fn resolve(spec: &'static Value, value: &'static Value) -> &'static Value {
+    match value["$ref"].as_str() {
+        Some(reference) => {
+            let pointer = reference.trim_start_matches('#');
+
+            match spec.pointer(pointer) {
+                Some(val) => resolve(spec, val),
+                None => panic!("Unresolved reference '{}'", reference),
+            }
+        }
+        None => value,
+    }
+}
+
+fn validate_schema(
+    spec: &'static Value,
+    schema: &'static Value,
+    value: &Value,
+    pointer: &str,
+    violations: &mut Vec<String>,
+) {
+    let schema = resolve(spec, schema);
+    let location = if pointer.is_empty() { "/" } else { pointer };
+    let expected_type = schema["type"].as_str();
+    let type_matches = match expected_type {
+        Some("object") => value.is_object(),
+        Some("array") => value.is_array(),
+        Some("string") => value.is_string(),
+        Some("integer") => value.is_i64() || value.is_u64(),
+        Some("number") => value.is_number(),
+        Some("boolean") => value.is_boolean(),
+        _ => true,
+    };
+
+    if !type_matches {
+        violations.push(format!(
+            "{}: expected {}, found {}",
+            location,
+            expected_type.unwrap_or_default(),
+            describe(value)
+        ));
+
+        return;
+    }
+
+    let format_violation = match (schema["format"].as_str(), value) {
+        (Some("int32"), Value::Number(number))
+            if number
+                .as_i64()
+                .and_then(|val| i32::try_from(val).ok())
+                .is_none() =>
+        {
+            Some(format!("{} is out of the int32 range", number))
+        }
+        (Some("uuid"), Value::String(string))
+            if Uuid::parse_str(string).is_err() =>
+        {
+            Some(format!("'{}' is not a UUID", string))
+        }
+        (Some("byte"), Value::String(string))
+            if base64::engine::general_purpose::STANDARD
+                .decode(string)
+                .is_err() =>
+        {
+            Some(format!(
+                "'{}' is not base64 encoded",
+                redaction::redact(string)
+            ))
+        }
+        _ => None,
+    };
+
+    if let Some(violation) = format_violation {
+        violations.push(format!("{}: {}", location, violation));
+    }
+
+    if let Value::Object(object) = value {
+        for field in schema["required"].as_array().into_iter().flatten() {
+            let field = field.as_str().unwrap_or_default();
+
+            if !object.contains_key(field) {
+                violations.push(format!(
+                    "{}: missing required field '{}'",
+                    location, field
+                ));
+            }
+        }
+
+        if let Some(properties) = schema["properties"].as_object() {
+            for (field, field_value) in object {
+                if let Some(field_schema) = properties.get(field) {
+                    validate_schema(
+                        spec,
+                        field_schema,
+                        field_value,
+                        &format!("{}/{}", pointer, field),
+                        violations,
+                    );
+                }
+            }
+        }
+    }
+
+    if let (Value::Array(items), Some(items_schema)) =
+        (value, schema.get("items"))
+    {
+        for (index, item) in items.iter().enumerate() {
+            validate_schema(
+                spec,
+                items_schema,
+                item,
+                &format!("{}/{}", pointer, index),
+                violations,
+            );
+        }
+    }
+}
+
+/// Describes a value of an unexpected type. Strings are redacted and
+/// containers are only named, as either may hold key material.
+fn describe(value: &Value) -> String {
+    match value {
+        Value::String(string) => format!("'{}'", redaction::redact(string)),
+        Value::Array(_) => "an array".to_string(),
+        Value::Object(_) => "an object".to_string(),
+        _ => value.to_string(),
+    }
+}
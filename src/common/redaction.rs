@@ -0,0 +1,106 @@
+// SPDX-FileCopyrightText: © 2023 Merqury Cybersecurity Ltd <info@merqury.eu>
+// SPDX-License-Identifier: PolyForm-Noncommercial-1.0.0
+
+//! Redaction of key material from everything the test suite prints or writes,
+//! so that runs against production KMEs do not leak keys into logs and
+//! reports.
+//!
+//! The policy is read from its environment variable on its own, rather than
+//! through `CONFIG`, so that models can be printed without a complete
+//! configuration, e.g. by the tests that do not require a KME.
+
+use super::config::ENV_KEY_REDACTION;
+use ring::digest;
+use serde_json::Value;
+use std::{env, str::FromStr};
+
+/// Number of characters kept by [`Redaction::Truncate`].
+static TRUNCATED_LENGTH: usize = 4;
+/// Number of bytes of the SHA-256 digest kept by [`Redaction::Hash`].
+static HASH_LENGTH: usize = 8;
+
+lazy_static! {
+    pub static ref REDACTION: Redaction = match env::var(ENV_KEY_REDACTION) {
+        Ok(val) => match val.parse() {
+            Ok(parsed_val) => parsed_val,
+            Err(e) => panic!(
+                "Environment variable '{}' has an invalid value '{}'. Error: \
+                 {:?}",
+                ENV_KEY_REDACTION, val, e
+            ),
+        },
+        Err(_) => Redaction::default(),
+    };
+}
+
+/// How key material is shown in diagnostics and reports.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Redaction {
+    /// Replaced by a prefix of its SHA-256 digest, so that equal keys can
+    /// still be told apart from different ones.
+    #[default]
+    Hash,
+    /// Replaced by its first few characters.
+    Truncate,
+    /// Shown as is.
+    Off,
+}
+
+impl FromStr for Redaction {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "hash" => Ok(Self::Hash),
+            "truncate" => Ok(Self::Truncate),
+            "off" => Ok(Self::Off),
+            _ => Err("expected one of 'hash', 'truncate' or 'off'".to_string()),
+        }
+    }
+}
+
+impl Redaction {
+    /// Redacts a single key, as found in the `key` field of a key container.
+    pub fn apply(&self, key: &str) -> String {
+        match self {
+            Redaction::Hash => {
+                let hash = digest::digest(&digest::SHA256, key.as_bytes());
+                let hex: String = hash.as_ref()[..HASH_LENGTH]
+                    .iter()
+                    .map(|val| format!("{:02x}", val))
+                    .collect();
+
+                format!("<redacted sha256:{}>", hex)
+            }
+            Redaction::Truncate => {
+                let prefix: String =
+                    key.chars().take(TRUNCATED_LENGTH).collect();
+
+                format!("<redacted {}... ({} chars)>", prefix, key.len())
+            }
+            Redaction::Off => key.to_string(),
+        }
+    }
+
+    /// Redacts the `key` of every element of the `keys` array of a JSON body,
+    /// returning whether there was any.
+    pub fn apply_to_body(&self, body: &mut Value) -> bool {
+        let Some(keys) = body.get_mut("keys").and_then(Value::as_array_mut)
+        else {
+            return false;
+        };
+
+        for element in keys {
+            if let Some(Value::String(key)) = element.get_mut("key") {
+                *key = self.apply(key);
+            }
+        }
+
+        true
+    }
+}
+
+/// Redacts a single key according to the configured policy.
+pub fn redact(key: &str) -> String {
+    REDACTION.apply(key)
+}
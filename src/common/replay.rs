@@ -0,0 +1,226 @@
+// SPDX-FileCopyrightText: © 2023 Merqury Cybersecurity Ltd <info@merqury.eu>
+// SPDX-License-Identifier: PolyForm-Noncommercial-1.0.0
+
+//! Replay of the exchanges recorded to a HAR file by [`super::har`], so that
+//! the checks can be re-evaluated offline against the responses of a past run.
+//!
+//! The exchanges of each check are replayed in the order they were recorded.
+//! A request is answered by the first exchange of its check not replayed yet
+//! that has the same method and URL path; the query string and body are not
+//! compared, as they may hold values generated at random by the check. Among
+//! those, an exchange naming the same key IDs is preferred, so that keys
+//! retrieved concurrently are each answered by their own retrieval.
+//! Exchanges that failed are replayed as the same failure.
+
+use super::{check_name, config::CONFIG, error::Error};
+use reqwest::{
+    header::{HeaderMap, HeaderName, HeaderValue},
+    Method, StatusCode, Url,
+};
+use serde_json::Value;
+use std::{
+    collections::{HashMap, VecDeque},
+    fs,
+    sync::Mutex,
+};
+
+lazy_static! {
+    /// The exchanges to replay, if replay is enabled.
+    pub static ref REPLAY: Option<Replay> =
+        CONFIG.replay.as_deref().map(Replay::load);
+}
+
+/// A recorded response.
+pub struct Recorded {
+    pub status: StatusCode,
+    pub headers: HeaderMap,
+    pub body: Vec<u8>,
+}
+
+/// The exchanges of a HAR file not replayed yet, by check.
+pub struct Replay {
+    entries: Mutex<HashMap<String, VecDeque<Value>>>,
+}
+
+impl Replay {
+    pub fn load(path: &str) -> Self {
+        let har =
+            match fs::read(path).map_err(|e| e.to_string()).and_then(|val| {
+                serde_json::from_slice::<Value>(&val).map_err(|e| e.to_string())
+            }) {
+                Ok(val) => val,
+                Err(e) => {
+                    panic!("Failed to read HAR file '{}'. Error: {}", path, e)
+                }
+            };
+
+        Self::new(har)
+    }
+
+    pub fn new(har: Value) -> Self {
+        let mut entries: HashMap<String, VecDeque<Value>> = HashMap::new();
+
+        for entry in har["log"]["entries"].as_array().into_iter().flatten() {
+            let check = entry["_check"].as_str().unwrap_or("<unnamed>");
+
+            entries
+                .entry(check.to_string())
+                .or_default()
+                .push_back(entry.clone());
+        }
+
+        Self {
+            entries: Mutex::new(entries),
+        }
+    }
+
+    /// Returns the recorded response to the request made by the current
+    /// check, the recorded failure, or why there is neither.
+    pub fn respond(
+        &self,
+        method: &Method,
+        url: &Url,
+        body: Option<&[u8]>,
+    ) -> Result<Recorded, Error> {
+        let check = check_name();
+        let requested_key_ids = key_ids(url, body);
+        let mut entries =
+            self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        let recorded = entries.get_mut(&check).and_then(|val| {
+            let candidates: Vec<(usize, Url)> = val
+                .iter()
+                .enumerate()
+                .filter(|(_, entry)| {
+                    entry["request"]["method"] == method.as_str()
+                })
+                .filter_map(|(index, entry)| {
+                    let recorded_url =
+                        Url::parse(entry["request"]["url"].as_str()?).ok()?;
+
+                    (recorded_url.path() == url.path())
+                        .then_some((index, recorded_url))
+                })
+                .collect();
+            let index = candidates
+                .iter()
+                .find(|(index, recorded_url)| {
+                    let recorded_body = val[*index]["request"]["postData"]
+                        ["text"]
+                        .as_str()
+                        .map(str::as_bytes);
+
+                    key_ids(recorded_url, recorded_body) == requested_key_ids
+                })
+                .or_else(|| candidates.first())?
+                .0;
+
+            val.remove(index)
+        });
+        let entry = match recorded {
+            Some(val) => val,
+            None => {
+                return Err(Error::Replay {
+                    url: url.to_string(),
+                    reason: format!(
+                        "no recorded exchange left for {} in '{}'",
+                        method, check
+                    ),
+                })
+            }
+        };
+
+        if let Some(error) = Self::recorded_error(&entry["_error"], url) {
+            return Err(error);
+        }
+
+        let response = &entry["response"];
+        let status = response["status"]
+            .as_u64()
+            .and_then(|val| u16::try_from(val).ok())
+            .and_then(|val| StatusCode::from_u16(val).ok())
+            .ok_or_else(|| Error::Replay {
+                url: url.to_string(),
+                reason: format!(
+                    "invalid recorded status {}",
+                    response["status"]
+                ),
+            })?;
+        let headers = response["headers"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|header| {
+                Some((
+                    HeaderName::from_bytes(header["name"].as_str()?.as_bytes())
+                        .ok()?,
+                    HeaderValue::from_str(header["value"].as_str()?).ok()?,
+                ))
+            })
+            .collect();
+        let body = response["content"]["text"]
+            .as_str()
+            .unwrap_or_default()
+            .as_bytes()
+            .to_vec();
+
+        Ok(Recorded {
+            status,
+            headers,
+            body,
+        })
+    }
+
+    /// Rebuilds a recorded failure.
+    fn recorded_error(error: &Value, url: &Url) -> Option<Error> {
+        let url = url.to_string();
+        let reason = error["reason"].as_str()?.to_string();
+
+        Some(match error["kind"].as_str() {
+            Some("tls") => Error::Tls { url, reason },
+            Some("protocol") => Error::Protocol { url, reason },
+            Some("config") => Error::Config(reason),
+            _ => Error::Transport {
+                url,
+                reason,
+                connected: error["connected"].as_bool().unwrap_or_default(),
+            },
+        })
+    }
+}
+
+/// The key IDs named by a request, in its query string or body, sorted.
+fn key_ids(url: &Url, body: Option<&[u8]>) -> Vec<String> {
+    fn collect(value: &Value, key_ids: &mut Vec<String>) {
+        match value {
+            Value::Object(object) => {
+                for (name, field) in object {
+                    match (name.as_str(), field) {
+                        ("key_ID", Value::String(val)) => {
+                            key_ids.push(val.clone())
+                        }
+                        _ => collect(field, key_ids),
+                    }
+                }
+            }
+            Value::Array(items) => {
+                items.iter().for_each(|item| collect(item, key_ids))
+            }
+            _ => {}
+        }
+    }
+
+    let mut key_ids: Vec<String> = url
+        .query_pairs()
+        .filter(|(name, _)| name == "key_ID")
+        .map(|(_, value)| value.into_owned())
+        .collect();
+
+    if let Some(body) =
+        body.and_then(|val| serde_json::from_slice::<Value>(val).ok())
+    {
+        collect(&body, &mut key_ids);
+    }
+
+    key_ids.sort();
+    key_ids
+}
@@ -0,0 +1,54 @@
+// SPDX-FileCopyrightText: © 2023 Merqury Cybersecurity Ltd <info@merqury.eu>
+// SPDX-License-Identifier: PolyForm-Noncommercial-1.0.0
+
+//! Versions of ETSI GS QKD 014 with a bundled OpenAPI document. A version
+//! selects the OpenAPI document against which the KME responses are
+//! validated, and nothing else.
+
+use serde::Serialize;
+use std::{fmt, str::FromStr};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize)]
+pub enum SpecVersion {
+    #[default]
+    #[serde(rename = "1.1.1")]
+    V1_1_1,
+}
+
+impl SpecVersion {
+    pub const ALL: &'static [Self] = &[Self::V1_1_1];
+
+    /// OpenAPI document describing the interface defined by this version.
+    pub fn openapi_document(self) -> &'static str {
+        match self {
+            Self::V1_1_1 => {
+                include_str!("../../openapi/etsi_gs_qkd_014_v1.1.1.yaml")
+            }
+        }
+    }
+}
+
+impl fmt::Display for SpecVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::V1_1_1 => write!(f, "1.1.1"),
+        }
+    }
+}
+
+impl FromStr for SpecVersion {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .iter()
+            .find(|val| val.to_string() == s.trim_start_matches('v'))
+            .copied()
+            .ok_or_else(|| {
+                let supported: Vec<String> =
+                    Self::ALL.iter().map(|val| val.to_string()).collect();
+
+                format!("expected one of: {}", supported.join(", "))
+            })
+    }
+}
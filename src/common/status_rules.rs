@@ -0,0 +1,178 @@
+// SPDX-FileCopyrightText: © 2023 Merqury Cybersecurity Ltd <info@merqury.eu>
+// SPDX-License-Identifier: PolyForm-Noncommercial-1.0.0
+
+//! Semantic rules a `Status` reply must satisfy beyond its schema. The rules
+//! are checked on the raw JSON value, so that integers the `Status` model
+//! cannot hold are reported rather than failing the whole reply.
+
+use serde_json::Value;
+use std::fmt;
+
+/// The integer fields of a `Status` reply.
+pub static INTEGER_FIELDS: [&str; 7] = [
+    "key_size",
+    "stored_key_count",
+    "max_key_count",
+    "max_key_per_request",
+    "max_key_size",
+    "min_key_size",
+    "max_SAE_ID_count",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rule {
+    /// `source_KME_ID` and `target_KME_ID` are not empty.
+    KmeIdsNotEmpty,
+    /// Every integer field is an integer that fits in 32 bits.
+    IntegersFitInI32,
+    /// `min_key_size <= key_size <= max_key_size`.
+    KeySizeWithinRange,
+    /// `key_size`, `min_key_size` and `max_key_size` are multiples of 8.
+    KeySizesMultipleOf8,
+    /// `stored_key_count <= max_key_count`.
+    StoredKeyCountWithinMax,
+    /// `max_key_per_request >= 1`.
+    MaxKeyPerRequestPositive,
+    /// `max_SAE_ID_count >= 0`.
+    MaxSaeIdCountNotNegative,
+}
+
+impl Rule {
+    pub const ALL: [Rule; 7] = [
+        Rule::KmeIdsNotEmpty,
+        Rule::IntegersFitInI32,
+        Rule::KeySizeWithinRange,
+        Rule::KeySizesMultipleOf8,
+        Rule::StoredKeyCountWithinMax,
+        Rule::MaxKeyPerRequestPositive,
+        Rule::MaxSaeIdCountNotNegative,
+    ];
+
+    /// Checks the rule against a `Status` reply, returning a description of
+    /// the violation if any.
+    ///
+    /// Fields that are missing or are not integers are left to the schema
+    /// and to `IntegersFitInI32`, so that a single problem is not reported by
+    /// every rule involving the field.
+    pub fn check(&self, status: &Value) -> Result<(), String> {
+        match self {
+            Rule::KmeIdsNotEmpty => {
+                let empty: Vec<&str> = ["source_KME_ID", "target_KME_ID"]
+                    .into_iter()
+                    .filter(|field| {
+                        status[field].as_str().is_some_and(str::is_empty)
+                    })
+                    .collect();
+
+                match empty.is_empty() {
+                    true => Ok(()),
+                    false => Err(format!("{} empty", empty.join(" and "))),
+                }
+            }
+            Rule::IntegersFitInI32 => {
+                let invalid: Vec<String> = INTEGER_FIELDS
+                    .into_iter()
+                    .filter(|field| {
+                        !status[field].is_null()
+                            && integer(status, field)
+                                .is_none_or(|val| i32::try_from(val).is_err())
+                    })
+                    .map(|field| format!("{} is {}", field, status[field]))
+                    .collect();
+
+                match invalid.is_empty() {
+                    true => Ok(()),
+                    false => Err(format!(
+                        "not a 32-bit integer: {}",
+                        invalid.join(", ")
+                    )),
+                }
+            }
+            Rule::KeySizeWithinRange => match (
+                integer(status, "min_key_size"),
+                integer(status, "key_size"),
+                integer(status, "max_key_size"),
+            ) {
+                (Some(min), Some(size), Some(max))
+                    if !(min <= size && size <= max) =>
+                {
+                    Err(format!(
+                        "key_size {} is not within min_key_size {} and \
+                         max_key_size {}",
+                        size, min, max
+                    ))
+                }
+                _ => Ok(()),
+            },
+            Rule::KeySizesMultipleOf8 => {
+                let unaligned: Vec<String> =
+                    ["key_size", "min_key_size", "max_key_size"]
+                        .into_iter()
+                        .filter_map(|field| {
+                            integer(status, field)
+                                .filter(|val| val % 8 != 0)
+                                .map(|val| format!("{} is {}", field, val))
+                        })
+                        .collect();
+
+                match unaligned.is_empty() {
+                    true => Ok(()),
+                    false => Err(format!(
+                        "not a multiple of 8: {}",
+                        unaligned.join(", ")
+                    )),
+                }
+            }
+            Rule::StoredKeyCountWithinMax => match (
+                integer(status, "stored_key_count"),
+                integer(status, "max_key_count"),
+            ) {
+                (Some(stored), Some(max)) if stored > max => Err(format!(
+                    "stored_key_count {} exceeds max_key_count {}",
+                    stored, max
+                )),
+                _ => Ok(()),
+            },
+            Rule::MaxKeyPerRequestPositive => {
+                match integer(status, "max_key_per_request") {
+                    Some(val) if val < 1 => {
+                        Err(format!("max_key_per_request is {}", val))
+                    }
+                    _ => Ok(()),
+                }
+            }
+            Rule::MaxSaeIdCountNotNegative => {
+                match integer(status, "max_SAE_ID_count") {
+                    Some(val) if val < 0 => {
+                        Err(format!("max_SAE_ID_count is {}", val))
+                    }
+                    _ => Ok(()),
+                }
+            }
+        }
+    }
+}
+
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let description = match self {
+            Rule::KmeIdsNotEmpty => "KME IDs are not empty",
+            Rule::IntegersFitInI32 => "integers fit in 32 bits",
+            Rule::KeySizeWithinRange => {
+                "min_key_size <= key_size <= max_key_size"
+            }
+            Rule::KeySizesMultipleOf8 => "key sizes are multiples of 8",
+            Rule::StoredKeyCountWithinMax => {
+                "stored_key_count <= max_key_count"
+            }
+            Rule::MaxKeyPerRequestPositive => "max_key_per_request >= 1",
+            Rule::MaxSaeIdCountNotNegative => "max_SAE_ID_count >= 0",
+        };
+
+        write!(f, "{}", description)
+    }
+}
+
+fn integer(status: &Value, field: &str) -> Option<i64> {
+    status[field].as_i64()
+}
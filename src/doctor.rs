@@ -0,0 +1,345 @@
+// SPDX-FileCopyrightText: © 2023 Merqury Cybersecurity Ltd <info@merqury.eu>
+// SPDX-License-Identifier: PolyForm-Noncommercial-1.0.0
+
+use etsi_gs_qkd_014_testsuite::{
+    common::{
+        self,
+        certificate::{Certificate, Identity},
+        config::{Config, SaeIdentity, Side, CONFIG},
+        error::Error,
+    },
+    models::status::Status,
+};
+use reqwest::Url;
+use std::net::ToSocketAddrs;
+
+/// Number of days before expiry from which a certificate is reported.
+static EXPIRY_WARNING_DAYS: i32 = 30;
+
+#[derive(clap::Args)]
+pub struct Args {
+    /// Only check the configuration and certificates, without connecting to
+    /// the KMEs.
+    #[arg(long)]
+    offline: bool,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Outcome {
+    Pass,
+    Warn,
+    Fail,
+    Skip,
+}
+
+/// The checks made so far, printed as they are made.
+#[derive(Default)]
+struct Checklist {
+    passed: usize,
+    warnings: usize,
+    failed: usize,
+}
+
+impl Checklist {
+    fn section(&self, title: &str) {
+        println!("{}", title);
+    }
+
+    fn item(&mut self, outcome: Outcome, description: impl AsRef<str>) {
+        let label = match outcome {
+            Outcome::Pass => {
+                self.passed += 1;
+                " OK "
+            }
+            Outcome::Warn => {
+                self.warnings += 1;
+                "WARN"
+            }
+            Outcome::Fail => {
+                self.failed += 1;
+                "FAIL"
+            }
+            Outcome::Skip => "SKIP",
+        };
+
+        println!("  [{}] {}", label, description.as_ref());
+    }
+
+    fn check<T>(
+        &mut self,
+        description: &str,
+        result: Result<T, Error>,
+    ) -> Option<T> {
+        match result {
+            Ok(val) => {
+                self.item(Outcome::Pass, description);
+                Some(val)
+            }
+            Err(e) => {
+                self.item(Outcome::Fail, format!("{}: {}", description, e));
+                None
+            }
+        }
+    }
+}
+
+/// Checks that the test suite can run against the configured KMEs, printing
+/// a checklist. Exits with status 1 if any check fails.
+pub async fn run(args: Args) -> Result<(), Error> {
+    // The checks use the blocking client, which must not run on the runtime.
+    let checklist = tokio::task::spawn_blocking(move || check_all(&args))
+        .await
+        .expect("The checks panicked");
+
+    println!(
+        "\n{} passed, {} warnings, {} failed",
+        checklist.passed, checklist.warnings, checklist.failed
+    );
+
+    if checklist.failed > 0 {
+        std::process::exit(1);
+    }
+
+    Ok(())
+}
+
+fn check_all(args: &Args) -> Checklist {
+    let mut checklist = Checklist::default();
+
+    checklist.section("Configuration");
+
+    // Nothing else can be checked without a complete configuration, which
+    // is loaded here rather than through `CONFIG` so that it does not panic.
+    if checklist.check("environment variables", Config::load()).is_none() {
+        return checklist;
+    }
+
+    checklist.section("Certificates");
+
+    for path in common::root_certificate_paths() {
+        check_root_certificate(&mut checklist, path);
+    }
+
+    for (name, sae) in &CONFIG.saes {
+        check_identity(&mut checklist, name, sae);
+    }
+
+    checklist.section("Network");
+
+    if args.offline {
+        checklist.item(Outcome::Skip, "network checks: '--offline' is set");
+    } else if let Some(path) = &CONFIG.replay {
+        checklist.item(
+            Outcome::Skip,
+            format!("network checks: replaying '{}'", path),
+        );
+    } else {
+        check_network(&mut checklist);
+    }
+
+    checklist
+}
+
+fn check_root_certificate(checklist: &mut Checklist, path: &str) {
+    let description = format!("root certificate '{}'", path);
+
+    if checklist
+        .check(
+            &format!("{} parses", description),
+            common::load_root_certificate(path),
+        )
+        .is_some()
+    {
+        check_validity(checklist, &description, Certificate::read(path));
+    }
+}
+
+fn check_identity(checklist: &mut Checklist, name: &str, sae: &SaeIdentity) {
+    let description = format!("SAE '{}' ({})", name, sae.id);
+
+    if checklist
+        .check(
+            &format!(
+                "{}: certificate and private key '{}' parse",
+                description,
+                sae.key.as_ref().unwrap_or(&sae.crt)
+            ),
+            common::load_identity(sae),
+        )
+        .is_none()
+    {
+        return;
+    }
+
+    let certificate = Identity::read(sae).and_then(|val| {
+        val.certificate().map_err(|reason| Error::Certificate {
+            path: sae.crt.clone(),
+            reason,
+        })
+    });
+
+    if let Some(certificate) =
+        check_validity(checklist, &description, certificate)
+    {
+        // The KME identifies the SAE by its certificate, so it would refuse
+        // one issued to another SAE.
+        match certificate.names_sae(&sae.id) {
+            true => checklist.item(
+                Outcome::Pass,
+                format!(
+                    "{}: certificate is issued to '{}'",
+                    description, sae.id
+                ),
+            ),
+            false => checklist.item(
+                Outcome::Fail,
+                format!(
+                    "{}: certificate subject '{}' does not name '{}'",
+                    description, certificate.subject, sae.id
+                ),
+            ),
+        }
+    }
+}
+
+/// Checks that the certificate is valid now and does not expire soon.
+fn check_validity(
+    checklist: &mut Checklist,
+    description: &str,
+    certificate: Result<Certificate, Error>,
+) -> Option<Certificate> {
+    let certificate = match certificate {
+        Ok(val) => val,
+        Err(e) => {
+            checklist.item(Outcome::Fail, format!("{}: {}", description, e));
+            return None;
+        }
+    };
+    let (outcome, validity) = if certificate.expired {
+        (
+            Outcome::Fail,
+            format!("expired on {}", certificate.not_after),
+        )
+    } else if certificate.not_yet_valid {
+        (
+            Outcome::Fail,
+            format!("not valid before {}", certificate.not_before),
+        )
+    } else if certificate.days_left < EXPIRY_WARNING_DAYS {
+        (
+            Outcome::Warn,
+            format!(
+                "expires in {} days, on {}",
+                certificate.days_left, certificate.not_after
+            ),
+        )
+    } else {
+        (
+            Outcome::Pass,
+            format!("valid until {}", certificate.not_after),
+        )
+    };
+
+    checklist.item(outcome, format!("{}: {}", description, validity));
+
+    Some(certificate)
+}
+
+fn check_network(checklist: &mut Checklist) {
+    let mut base_urls = vec![&CONFIG.base_url];
+
+    if CONFIG.is_multi_kme() {
+        base_urls.push(&CONFIG.slave_base_url);
+    }
+
+    for base_url in base_urls {
+        checklist.check(&format!("'{}' resolves", base_url), resolve(base_url));
+    }
+
+    for (name, sae) in &CONFIG.saes {
+        let side = CONFIG.side(sae);
+        let peer = match side {
+            Side::Master => CONFIG.slave(),
+            Side::Slave => CONFIG.master(),
+        };
+        let base_url = CONFIG.base_url_of(side);
+        let url = format!("{}/{}/status", base_url, peer.id);
+        let description = format!(
+            "TLS handshake as SAE '{}' ({}) with '{}'",
+            name, sae.id, base_url
+        );
+
+        // The certificate failures have been reported already.
+        if common::load_identity(sae).is_err() {
+            checklist.item(
+                Outcome::Skip,
+                format!("{}: the certificate cannot be loaded", description),
+            );
+            continue;
+        }
+
+        // Any reply, even a refusal, shows that the handshake succeeded.
+        checklist.check(
+            &description,
+            common::build_client(sae, side)
+                .and_then(|client| client.get(&url).send()),
+        );
+    }
+
+    check_status(checklist, Side::Master, CONFIG.master(), CONFIG.slave());
+
+    if CONFIG.is_multi_kme() {
+        check_status(checklist, Side::Slave, CONFIG.slave(), CONFIG.master());
+    }
+}
+
+fn resolve(base_url: &str) -> Result<(), Error> {
+    let url = Url::parse(base_url).map_err(|e| {
+        Error::Config(format!("invalid base URL '{}': {}", base_url, e))
+    })?;
+    let transport_error = |reason: String| Error::Transport {
+        url: base_url.to_string(),
+        reason,
+        connected: false,
+    };
+    let host =
+        url.host_str().ok_or_else(|| transport_error("no host".to_string()))?;
+    let port = url.port_or_known_default().unwrap_or(443);
+    let mut addrs = (host, port)
+        .to_socket_addrs()
+        .map_err(|e| transport_error(e.to_string()))?;
+
+    match addrs.next() {
+        Some(_) => Ok(()),
+        None => Err(transport_error("no address found".to_string())),
+    }
+}
+
+/// Checks that `/status` replies with a valid status when queried by the SAE
+/// at the KME of the given side.
+fn check_status(
+    checklist: &mut Checklist,
+    side: Side,
+    sae: &SaeIdentity,
+    peer: &SaeIdentity,
+) {
+    let url = format!("{}/{}/status", CONFIG.base_url_of(side), peer.id);
+    let role = match side {
+        Side::Master => "master",
+        Side::Slave => "slave",
+    };
+    let status = common::build_client(sae, side)
+        .and_then(|client| client.get(&url).send())
+        .and_then(|response| match response.status().is_success() {
+            true => response.json::<Status>(),
+            false => Err(Error::Protocol {
+                url: url.clone(),
+                reason: format!("returned status {}", response.status()),
+            }),
+        });
+
+    checklist.check(
+        &format!("'{}' replies to the {} SAE ({})", url, role, sae.id),
+        status,
+    );
+}
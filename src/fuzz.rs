@@ -0,0 +1,747 @@
+// SPDX-FileCopyrightText: © 2023 Merqury Cybersecurity Ltd <info@merqury.eu>
+// SPDX-License-Identifier: PolyForm-Noncommercial-1.0.0
+
+use crate::metrics;
+use clap::builder::RangedU64ValueParser;
+use etsi_gs_qkd_014_testsuite::{
+    common::{
+        self,
+        client::AsyncClient,
+        config::{Side, CONFIG},
+        error::Error,
+        spec::SpecVersion,
+    },
+    models::{error_message::ErrorMessage, key_request::KeyRequest},
+};
+use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
+use reqwest::{Method, StatusCode};
+use serde::Serialize;
+use serde_json::{json, Map, Value};
+use std::{
+    collections::{BTreeMap, HashSet},
+    fs::{self, File},
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+use uuid::Uuid;
+
+/// Maximum number of requests sent while minimizing a single finding.
+static MAX_MINIMIZATION_ATTEMPTS: usize = 200;
+/// Maximum time spent minimizing a single finding.
+static MAX_MINIMIZATION_TIME: Duration = Duration::from_secs(60);
+/// Number of keys retrieved from the KME whose IDs are used in the key IDs
+/// requests.
+static REAL_KEY_IDS: usize = 8;
+/// Maximum depth of the randomly generated JSON values.
+static MAX_VALUE_DEPTH: usize = 3;
+/// Maximum depth used when wrapping a value in nested arrays or objects.
+static MAX_NESTING_DEPTH: usize = 128;
+/// Maximum length of a serialized request body.
+static MAX_BODY_LENGTH: usize = 1024 * 1024;
+/// Maximum length of a query parameter name or value, keeping the URL within
+/// the limits of the HTTP client.
+static MAX_QUERY_VALUE_LENGTH: usize = 4096;
+
+static EXTREME_NUMBERS: [fn() -> Value; 14] = [
+    || json!(0),
+    || json!(-1),
+    || json!(i32::MAX),
+    || json!(i32::MIN),
+    || json!(i32::MAX as i64 + 1),
+    || json!(i32::MIN as i64 - 1),
+    || json!(i64::MAX),
+    || json!(i64::MIN),
+    || json!(u64::MAX),
+    || json!(f64::MAX),
+    || json!(-f64::MAX),
+    || json!(0.5),
+    || json!(256.0),
+    || json!(-0.0),
+];
+
+static UNUSUAL_STRINGS: [&str; 14] = [
+    "",
+    " ",
+    "\0",
+    "\u{feff}",
+    "\u{202e}sae_001",
+    "e\u{0301}",
+    "\u{1f511}",
+    "\u{10ffff}",
+    "\u{fffd}",
+    "1e3",
+    "0x100",
+    "../../status",
+    "%00",
+    "'\"; --",
+];
+
+#[derive(clap::Args)]
+pub struct Args {
+    /// Seed of the random number generator. Runs using the same seed send
+    /// the same requests. A random seed is used when not set.
+    #[arg(long)]
+    seed: Option<u64>,
+    /// Number of requests to generate.
+    #[arg(
+        long,
+        default_value_t = 1000,
+        value_parser = RangedU64ValueParser::<usize>::new().range(1..)
+    )]
+    iterations: usize,
+    /// Time, in seconds, after which a request is considered to have timed
+    /// out.
+    #[arg(
+        long,
+        default_value_t = 10,
+        value_parser = clap::value_parser!(u64).range(1..)
+    )]
+    timeout: u64,
+    /// Directory in which the reproducers of the findings are saved.
+    #[arg(long, default_value = "fuzz_findings")]
+    output: PathBuf,
+}
+
+#[derive(Clone, Copy, Serialize)]
+#[serde(rename_all = "snake_case")]
+enum Endpoint {
+    EncKeys,
+    DecKeys,
+}
+
+#[derive(Clone, Serialize)]
+struct FuzzRequest {
+    endpoint: Endpoint,
+    method: String,
+    query: Vec<(String, String)>,
+    body: Option<Value>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+enum Finding {
+    ServerError {
+        status: u16,
+    },
+    Timeout,
+    ConnectionError {
+        error: String,
+    },
+    NonJsonErrorBody {
+        status: u16,
+        body: String,
+    },
+    /// The response does not conform to the OpenAPI document, reported when
+    /// OpenAPI validation is set to fail.
+    NonConformingResponse {
+        reason: String,
+    },
+}
+
+#[derive(Serialize)]
+struct Reproducer<'a> {
+    spec_version: SpecVersion,
+    seed: u64,
+    iteration: usize,
+    finding: &'a Finding,
+    request: &'a FuzzRequest,
+    original_request: &'a FuzzRequest,
+}
+
+struct Target {
+    master_client: AsyncClient,
+    slave_client: AsyncClient,
+    timeout: Duration,
+}
+
+pub async fn run(args: Args) -> Result<(), Error> {
+    let seed = args.seed.unwrap_or_else(rand::random);
+    let mut rng = StdRng::seed_from_u64(seed);
+    let target = Target {
+        master_client: common::build_async_client(
+            CONFIG.master(),
+            Side::Master,
+        )?,
+        slave_client: common::build_async_client(CONFIG.slave(), Side::Slave)?,
+        timeout: Duration::from_secs(args.timeout),
+    };
+    let key_size = metrics::fetch_status(&target.master_client).await?.key_size;
+    let key_ids = target.fetch_key_ids().await;
+    let mut findings: BTreeMap<&'static str, usize> = BTreeMap::new();
+
+    println!(
+        "Spec version: {}, seed: {} (use '--seed {}' to reproduce this run)",
+        CONFIG.spec_version, seed, seed
+    );
+
+    for iteration in 0..args.iterations {
+        let request = generate_request(&mut rng, key_size, &key_ids);
+        let finding = match target.send(&request).await {
+            Some(val) => val,
+            None => continue,
+        };
+        let minimized = target.minimize(&request, &finding).await;
+        let path = args.output.join(format!(
+            "{:06}-{}.json",
+            iteration,
+            finding.kind()
+        ));
+
+        println!(
+            "[{}] {}. Reproducer: {}",
+            iteration,
+            finding.kind(),
+            path.display()
+        );
+
+        write_reproducer(
+            &path,
+            &Reproducer {
+                spec_version: CONFIG.spec_version,
+                seed,
+                iteration,
+                finding: &finding,
+                request: &minimized,
+                original_request: &request,
+            },
+        )?;
+
+        *findings.entry(finding.kind()).or_default() += 1;
+    }
+
+    println!("Requests: {}", args.iterations);
+
+    for (kind, count) in &findings {
+        println!("  finding: {} x {}", kind, count);
+    }
+
+    if !findings.is_empty() {
+        std::process::exit(1);
+    }
+
+    Ok(())
+}
+
+fn write_reproducer(path: &Path, reproducer: &Reproducer) -> Result<(), Error> {
+    let reproducer_error = |reason: String| {
+        Error::Config(format!(
+            "cannot write the reproducer '{}': {}",
+            path.display(),
+            reason
+        ))
+    };
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .map_err(|e| reproducer_error(e.to_string()))?;
+    }
+
+    let file =
+        File::create(path).map_err(|e| reproducer_error(e.to_string()))?;
+
+    serde_json::to_writer_pretty(file, reproducer)
+        .map_err(|e| reproducer_error(e.to_string()))
+}
+
+impl Finding {
+    fn from_error(error: &Error) -> Self {
+        match error {
+            Error::Transport { reason, .. } if reason == "timed out" => {
+                Finding::Timeout
+            }
+            Error::Protocol { reason, .. } => Finding::NonConformingResponse {
+                reason: reason.clone(),
+            },
+            _ => Finding::ConnectionError {
+                error: error.to_string(),
+            },
+        }
+    }
+
+    fn kind(&self) -> &'static str {
+        match self {
+            Finding::ServerError { .. } => "server_error",
+            Finding::Timeout => "timeout",
+            Finding::ConnectionError { .. } => "connection_error",
+            Finding::NonJsonErrorBody { .. } => "non_json_error_body",
+            Finding::NonConformingResponse { .. } => "non_conforming_response",
+        }
+    }
+}
+
+impl FuzzRequest {
+    /// Size used to ensure that minimization always makes progress.
+    fn size(&self) -> usize {
+        let query_size: usize = self
+            .query
+            .iter()
+            .map(|(name, value)| name.len() + value.len() + 1)
+            .sum();
+
+        query_size + self.body.as_ref().map_or(0, |val| val.to_string().len())
+    }
+}
+
+impl Target {
+    /// Retrieves keys from the KME, one request at a time, and returns their
+    /// IDs. Keys that cannot be retrieved are left out, so that the requests
+    /// fall back to random key IDs.
+    async fn fetch_key_ids(&self) -> Vec<Uuid> {
+        let enc_keys_url =
+            format!("{}/{}/enc_keys", CONFIG.base_url, CONFIG.slave().id);
+        let mut key_ids = Vec::new();
+
+        for _ in 0..REAL_KEY_IDS {
+            let request = self
+                .master_client
+                .get(&enc_keys_url)
+                .query(&[("number", 1)])
+                .timeout(self.timeout);
+
+            if let Ok(container) = metrics::send_key_request(request).await {
+                key_ids.extend(container.keys.iter().map(|key| key.key_id));
+            }
+        }
+
+        key_ids
+    }
+
+    /// Sends the request and returns the finding it triggered, if any.
+    async fn send(&self, request: &FuzzRequest) -> Option<Finding> {
+        let (client, url) = match request.endpoint {
+            Endpoint::EncKeys => (
+                &self.master_client,
+                format!("{}/{}/enc_keys", CONFIG.base_url, CONFIG.slave().id),
+            ),
+            Endpoint::DecKeys => (
+                &self.slave_client,
+                format!(
+                    "{}/{}/dec_keys",
+                    CONFIG.slave_base_url,
+                    CONFIG.master().id
+                ),
+            ),
+        };
+        let mut request_builder = client
+            .request(
+                Method::from_bytes(request.method.as_bytes()).unwrap(),
+                url,
+            )
+            .query(&request.query)
+            .timeout(self.timeout);
+
+        if let Some(body) = &request.body {
+            request_builder = request_builder.json(body);
+        }
+
+        let response = match request_builder.send().await {
+            Ok(val) => val,
+            Err(e) => return Some(Finding::from_error(&e)),
+        };
+        let status = response.status();
+        let body = response.bytes();
+
+        // The KME may reply 503 when it is temporarily unable to serve keys,
+        // which is defined by the specification, unlike other server errors.
+        if status.is_server_error() && status != StatusCode::SERVICE_UNAVAILABLE
+        {
+            Some(Finding::ServerError {
+                status: status.as_u16(),
+            })
+        } else if !status.is_success()
+            && serde_json::from_slice::<ErrorMessage>(body).is_err()
+        {
+            Some(Finding::NonJsonErrorBody {
+                status: status.as_u16(),
+                body: String::from_utf8_lossy(body).into_owned(),
+            })
+        } else {
+            None
+        }
+    }
+
+    /// Repeatedly removes or simplifies parts of the request for as long as
+    /// the same kind of finding is still triggered, within a limited number
+    /// of attempts and time.
+    async fn minimize(
+        &self,
+        request: &FuzzRequest,
+        finding: &Finding,
+    ) -> FuzzRequest {
+        let mut minimized = request.clone();
+        let mut attempted = HashSet::new();
+        let mut attempts = 0;
+        let deadline = Instant::now() + MAX_MINIMIZATION_TIME;
+
+        'shrink: while attempts < MAX_MINIMIZATION_ATTEMPTS {
+            for shrink in shrink_candidates(&minimized) {
+                let candidate = shrink.apply(&minimized);
+
+                if candidate.size() >= minimized.size()
+                    || !attempted
+                        .insert(serde_json::to_string(&candidate).unwrap())
+                {
+                    continue;
+                }
+
+                attempts += 1;
+
+                if let Some(candidate_finding) = self.send(&candidate).await {
+                    if candidate_finding.kind() == finding.kind() {
+                        minimized = candidate;
+                        continue 'shrink;
+                    }
+                }
+
+                if attempts >= MAX_MINIMIZATION_ATTEMPTS
+                    || Instant::now() >= deadline
+                {
+                    break 'shrink;
+                }
+            }
+
+            break;
+        }
+
+        minimized
+    }
+}
+
+/// Generates a request that starts from a valid key request (or key IDs)
+/// structure, requesting keys of `key_size` bits, and has a few random
+/// mutations applied to it. Half of the key IDs requests use one of
+/// `key_ids`, when any, instead of a random key ID.
+fn generate_request(
+    rng: &mut StdRng,
+    key_size: i64,
+    key_ids: &[Uuid],
+) -> FuzzRequest {
+    let endpoint = *[Endpoint::EncKeys, Endpoint::DecKeys].choose(rng).unwrap();
+    let key_id = match key_ids.choose(rng) {
+        Some(val) if rng.gen_bool(0.5) => *val,
+        _ => Uuid::from_bytes(rng.gen()),
+    };
+
+    if rng.gen_bool(0.5) {
+        let mut query = match endpoint {
+            Endpoint::EncKeys => vec![
+                ("number".to_string(), "1".to_string()),
+                ("size".to_string(), key_size.to_string()),
+            ],
+            Endpoint::DecKeys => {
+                vec![("key_ID".to_string(), key_id.to_string())]
+            }
+        };
+
+        for _ in 0..rng.gen_range(1..=3) {
+            mutate_query(&mut query, rng);
+        }
+
+        FuzzRequest {
+            endpoint,
+            method: Method::GET.to_string(),
+            query,
+            body: None,
+        }
+    } else {
+        let additional_slave_sae_id = CONFIG
+            .extra_slaves()
+            .first()
+            .map_or(&CONFIG.slave().id, |sae| &sae.id);
+        let mut body = match endpoint {
+            Endpoint::EncKeys => serde_json::to_value(KeyRequest {
+                number: Some(1),
+                size: Some(key_size),
+                additional_slave_sae_ids: Some(vec![
+                    additional_slave_sae_id.clone()
+                ]),
+                extension_mandatory: Some(Vec::new()),
+                extension_optional: Some(Vec::new()),
+            })
+            .unwrap(),
+            Endpoint::DecKeys => json!({
+                "key_IDs": [{
+                    "key_ID": key_id,
+                    "key_ID_extension": {},
+                }],
+                "key_IDs_extension": {},
+            }),
+        };
+
+        for _ in 0..rng.gen_range(1..=4) {
+            let mut mutated = body.clone();
+
+            mutate_value(&mut mutated, rng);
+
+            // Mutations can compound, e.g. by repeatedly duplicating nested
+            // arrays, so the ones producing oversized bodies are discarded.
+            if mutated.to_string().len() <= MAX_BODY_LENGTH {
+                body = mutated;
+            }
+        }
+
+        FuzzRequest {
+            endpoint,
+            method: Method::POST.to_string(),
+            query: Vec::new(),
+            body: Some(body),
+        }
+    }
+}
+
+fn mutate_query(query: &mut Vec<(String, String)>, rng: &mut StdRng) {
+    let value: String = match random_value(rng, 0) {
+        Value::String(val) => val,
+        val => val.to_string(),
+    }
+    .chars()
+    .take(MAX_QUERY_VALUE_LENGTH)
+    .collect();
+
+    match rng.gen_range(0..3) {
+        0 if !query.is_empty() => {
+            let index = rng.gen_range(0..query.len());
+            query[index].1 = value;
+        }
+        1 if !query.is_empty() => {
+            let index = rng.gen_range(0..query.len());
+            query.push((query[index].0.clone(), value));
+        }
+        _ => {
+            let name = random_string(rng)
+                .chars()
+                .take(MAX_QUERY_VALUE_LENGTH)
+                .collect();
+            query.push((name, value));
+        }
+    }
+}
+
+/// Applies a single mutation to a randomly chosen node of the JSON value.
+fn mutate_value(value: &mut Value, rng: &mut StdRng) {
+    let paths = node_paths(value);
+    let path = paths.choose(rng).unwrap();
+    let node = value.pointer_mut(path).unwrap();
+
+    match rng.gen_range(0..6) {
+        0 => *node = random_value(rng, MAX_VALUE_DEPTH),
+        1 => *node = EXTREME_NUMBERS.choose(rng).unwrap()(),
+        2 => *node = Value::String(random_string(rng)),
+        3 => {
+            let mut nested = node.take();
+
+            for _ in 0..rng.gen_range(1..=MAX_NESTING_DEPTH) {
+                nested = if rng.gen_bool(0.5) {
+                    json!([nested])
+                } else {
+                    json!({ "a": nested })
+                };
+            }
+
+            *node = nested;
+        }
+        4 => match node {
+            Value::Object(map) if !map.is_empty() => {
+                let key = map.keys().nth(rng.gen_range(0..map.len())).cloned();
+                map.remove(&key.unwrap());
+            }
+            Value::Object(map) => {
+                map.insert(random_string(rng), random_value(rng, 1));
+            }
+            Value::Array(elements) if !elements.is_empty() => {
+                let element = elements[0].clone();
+                elements.resize(rng.gen_range(2..=1024), element);
+            }
+            _ => *node = Value::Null,
+        },
+        _ => {
+            if let Value::Object(map) = node {
+                map.insert(random_string(rng), random_value(rng, 1));
+            } else {
+                let duplicated = node.take();
+                *node = json!([duplicated.clone(), duplicated]);
+            }
+        }
+    }
+}
+
+fn random_value(rng: &mut StdRng, depth: usize) -> Value {
+    match rng.gen_range(0..7) {
+        0 => Value::Null,
+        1 => Value::Bool(rng.gen()),
+        2 => EXTREME_NUMBERS.choose(rng).unwrap()(),
+        3 => json!(rng.gen_range(-1024..=1024)),
+        4 if depth > 0 => Value::Array(
+            (0..rng.gen_range(0..4))
+                .map(|_| random_value(rng, depth - 1))
+                .collect(),
+        ),
+        5 if depth > 0 => Value::Object(
+            (0..rng.gen_range(0..4))
+                .map(|_| (random_string(rng), random_value(rng, depth - 1)))
+                .collect::<Map<String, Value>>(),
+        ),
+        _ => Value::String(random_string(rng)),
+    }
+}
+
+fn random_string(rng: &mut StdRng) -> String {
+    match rng.gen_range(0..4) {
+        0 => UNUSUAL_STRINGS.choose(rng).unwrap().to_string(),
+        1 => "a".repeat(rng.gen_range(1..=65536)),
+        2 => (0..rng.gen_range(1..=16)).map(|_| rng.gen::<char>()).collect(),
+        _ => Uuid::from_bytes(rng.gen()).to_string(),
+    }
+}
+
+/// Returns the JSON pointers of every node of the value, root included.
+fn node_paths(value: &Value) -> Vec<String> {
+    let mut paths = vec![String::new()];
+    let mut index = 0;
+
+    while index < paths.len() {
+        let path = paths[index].clone();
+
+        match value.pointer(&path).unwrap() {
+            Value::Object(map) => {
+                for key in map.keys() {
+                    let escaped = key.replace('~', "~0").replace('/', "~1");
+                    paths.push(format!("{}/{}", path, escaped));
+                }
+            }
+            Value::Array(elements) => {
+                for element_index in 0..elements.len() {
+                    paths.push(format!("{}/{}", path, element_index));
+                }
+            }
+            _ => {}
+        }
+
+        index += 1;
+    }
+
+    paths
+}
+
+/// A single way of making a request smaller.
+enum Shrink {
+    RemoveQueryParameter(usize),
+    ShortenQueryName(usize),
+    ShortenQueryValue(usize),
+    /// Removes the node at the given JSON pointer from its parent.
+    RemoveNode(String),
+    /// Keeps the first half of an array or a string.
+    FirstHalf(String),
+    /// Keeps the second half of an array.
+    SecondHalf(String),
+    /// Replaces an array or object with its first element.
+    FirstChild(String),
+}
+
+/// Returns the ways in which the request can be made smaller. Changes closer
+/// to the root come first, as they tend to remove the most.
+fn shrink_candidates(request: &FuzzRequest) -> Vec<Shrink> {
+    let mut candidates = Vec::new();
+
+    for index in 0..request.query.len() {
+        candidates.push(Shrink::RemoveQueryParameter(index));
+        candidates.push(Shrink::ShortenQueryName(index));
+        candidates.push(Shrink::ShortenQueryValue(index));
+    }
+
+    if let Some(body) = &request.body {
+        for path in node_paths(body) {
+            if !path.is_empty() {
+                candidates.push(Shrink::RemoveNode(path.clone()));
+            }
+
+            match body.pointer(&path).unwrap() {
+                Value::Array(elements) if !elements.is_empty() => {
+                    candidates.push(Shrink::FirstHalf(path.clone()));
+                    candidates.push(Shrink::SecondHalf(path.clone()));
+                    candidates.push(Shrink::FirstChild(path));
+                }
+                Value::Object(map) if !map.is_empty() => {
+                    candidates.push(Shrink::FirstChild(path))
+                }
+                Value::String(val) if !val.is_empty() => {
+                    candidates.push(Shrink::FirstHalf(path))
+                }
+                _ => {}
+            }
+        }
+    }
+
+    candidates
+}
+
+impl Shrink {
+    /// Returns the shrunk request. The request is returned unchanged if the
+    /// shrink does not apply to it.
+    fn apply(&self, request: &FuzzRequest) -> FuzzRequest {
+        let mut shrunk = request.clone();
+
+        match self {
+            Shrink::RemoveQueryParameter(index) => {
+                shrunk.query.remove(*index);
+            }
+            Shrink::ShortenQueryName(index) => {
+                let name = &mut shrunk.query[*index].0;
+                *name = first_half(name);
+            }
+            Shrink::ShortenQueryValue(index) => {
+                let value = &mut shrunk.query[*index].1;
+                *value = first_half(value);
+            }
+            Shrink::RemoveNode(path) => {
+                let body = shrunk.body.as_mut().unwrap();
+                let (parent_path, last) = path.rsplit_once('/').unwrap();
+
+                match body.pointer_mut(parent_path).unwrap() {
+                    Value::Object(map) => {
+                        map.remove(&last.replace("~1", "/").replace("~0", "~"));
+                    }
+                    Value::Array(elements) => {
+                        elements.remove(last.parse::<usize>().unwrap());
+                    }
+                    _ => unreachable!(),
+                }
+            }
+            Shrink::FirstHalf(path) => {
+                match shrunk.body.as_mut().unwrap().pointer_mut(path).unwrap() {
+                    Value::Array(elements) => {
+                        elements.truncate(elements.len() / 2)
+                    }
+                    Value::String(val) => *val = first_half(val),
+                    _ => {}
+                }
+            }
+            Shrink::SecondHalf(path) => {
+                if let Value::Array(elements) =
+                    shrunk.body.as_mut().unwrap().pointer_mut(path).unwrap()
+                {
+                    elements.drain(..elements.len() / 2);
+                }
+            }
+            Shrink::FirstChild(path) => {
+                let node =
+                    shrunk.body.as_mut().unwrap().pointer_mut(path).unwrap();
+                let child = match node {
+                    Value::Array(elements) => elements.first().cloned(),
+                    Value::Object(map) => map.values().next().cloned(),
+                    _ => None,
+                };
+
+                if let Some(child) = child {
+                    *node = child;
+                }
+            }
+        }
+
+        shrunk
+    }
+}
+
+fn first_half(val: &str) -> String {
+    val.chars().take(val.chars().count() / 2).collect()
+}
@@ -0,0 +1,8 @@
+// SPDX-FileCopyrightText: © 2023 Merqury Cybersecurity Ltd <info@merqury.eu>
+// SPDX-License-Identifier: PolyForm-Noncommercial-1.0.0
+
+#[macro_use]
+extern crate lazy_static;
+
+pub mod common;
+pub mod models;
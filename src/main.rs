@@ -1,6 +1,77 @@
 // SPDX-FileCopyrightText: © 2023 Merqury Cybersecurity Ltd <info@merqury.eu>
 // SPDX-License-Identifier: PolyForm-Noncommercial-1.0.0
 
+mod benchmark;
+mod doctor;
+mod fuzz;
+mod metrics;
+mod soak;
+
+use clap::{Parser, Subcommand};
+use etsi_gs_qkd_014_testsuite::common::{
+    config::ENV_SPEC_VERSION, spec::SpecVersion,
+};
+
+/// Runs additional workloads against an ETSI GS QKD 014 Key Management Entity
+/// (KME). The conformance tests themselves are run using `cargo test`.
+///
+/// The KME and SAE details are read from the same environment variables used
+/// by the conformance tests.
+#[derive(Parser)]
+#[command(version, about)]
+struct Cli {
+    /// Version of ETSI GS QKD 014 whose OpenAPI document the responses are
+    /// validated against. Defaults to `ETSI_014_TEST_SUITE_SPEC_VERSION`, or
+    /// to 1.1.1 when not set.
+    #[arg(long, global = true)]
+    spec_version: Option<SpecVersion>,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Drives `enc_keys` and `dec_keys` with sustained traffic and reports
+    /// throughput, latency and errors.
+    Benchmark(benchmark::Args),
+    /// Checks the configuration, the certificates and the connectivity to the
+    /// KMEs, printing a checklist. Run it first when the tests cannot start.
+    Doctor(doctor::Args),
+    /// Sends randomized and mutated requests to `enc_keys` and `dec_keys`,
+    /// saving a minimized reproducer for every misbehaviour found.
+    Fuzz(fuzz::Args),
+    /// Repeatedly requests and retrieves keys over a long period, recording
+    /// how the KME behaves over time.
+    Soak(soak::Args),
+}
+
 fn main() {
-    println!("Hello, world!");
+    let cli = Cli::parse();
+
+    // The configuration is read from the environment on first use, so the
+    // option is applied to it while the process is still single threaded,
+    // before the runtime starts its worker threads.
+    if let Some(spec_version) = cli.spec_version {
+        std::env::set_var(ENV_SPEC_VERSION, spec_version.to_string());
+    }
+
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+    let result = runtime.block_on(async {
+        match cli.command {
+            Command::Benchmark(args) => benchmark::run(args).await,
+            Command::Doctor(args) => doctor::run(args).await,
+            Command::Fuzz(args) => fuzz::run(args).await,
+            Command::Soak(args) => soak::run(args).await,
+        }
+    });
+
+    // Errors of the suite itself, such as an unreachable KME, are told apart
+    // from the findings of the workloads, which exit with status 1.
+    if let Err(e) = result {
+        eprintln!("Error: {}", e);
+        std::process::exit(2);
+    }
 }
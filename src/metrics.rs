@@ -0,0 +1,75 @@
+// SPDX-FileCopyrightText: © 2023 Merqury Cybersecurity Ltd <info@merqury.eu>
+// SPDX-License-Identifier: PolyForm-Noncommercial-1.0.0
+
+use etsi_gs_qkd_014_testsuite::{
+    common::{
+        client::{AsyncClient, AsyncRequestBuilder},
+        config::CONFIG,
+        error::Error,
+    },
+    models::{key::KeyContainer, status::Status},
+};
+use std::time::Duration;
+
+/// Retrieves the `/status` of the slave SAE as the master SAE, from which the
+/// workloads derive the keys they request.
+pub async fn fetch_status(client: &AsyncClient) -> Result<Status, Error> {
+    let status_url =
+        format!("{}/{}/status", CONFIG.base_url, CONFIG.slave().id);
+    let response = client.get(&status_url).send().await?;
+
+    if !response.status().is_success() {
+        return Err(Error::Protocol {
+            url: status_url,
+            reason: format!(
+                "failed to retrieve '/status', returned status {}",
+                response.status()
+            ),
+        });
+    }
+
+    response.json::<Status>()
+}
+
+/// Sends a request to `enc_keys` or `dec_keys` and parses the returned keys.
+///
+/// Failures are mapped to a short label, such as the returned HTTP status, so
+/// that they can be grouped in reports.
+pub async fn send_key_request(
+    request: AsyncRequestBuilder,
+) -> Result<KeyContainer, String> {
+    match request.send().await {
+        Ok(response) if response.status().is_success() => response
+            .json::<KeyContainer>()
+            .map_err(|_| "invalid response body".to_string()),
+        Ok(response) => Err(response.status().to_string()),
+        Err(e) => Err(error_label(&e)),
+    }
+}
+
+/// Returns a short label for a failure to exchange a request with the KME.
+pub fn error_label(error: &Error) -> String {
+    match error {
+        Error::Transport { reason, .. } if reason == "timed out" => {
+            "timeout".to_string()
+        }
+        Error::Transport {
+            connected: false, ..
+        } => "connection error".to_string(),
+        Error::Transport { .. } => "transport error".to_string(),
+        _ => format!("{} error", error.kind()),
+    }
+}
+
+/// Returns the given percentile, in milliseconds, of the sorted latencies
+/// using the nearest-rank method.
+pub fn percentile(sorted_latencies: &[Duration], percentile: f64) -> f64 {
+    if sorted_latencies.is_empty() {
+        return 0.0;
+    }
+
+    let rank = (percentile / 100.0 * sorted_latencies.len() as f64).ceil();
+    let index = (rank as usize).clamp(1, sorted_latencies.len()) - 1;
+
+    sorted_latencies[index].as_secs_f64() * 1000.0
+}
@@ -0,0 +1,14 @@
+// SPDX-FileCopyrightText: © 2023 Merqury Cybersecurity Ltd <info@merqury.eu>
+// SPDX-License-Identifier: PolyForm-Noncommercial-1.0.0
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub struct ErrorMessage {
+    pub message: String,
+    /// Objects, as defined by the standard. Error messages whose details are
+    /// strings are not valid.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub details: Option<Vec<Map<String, Value>>>,
+}
@@ -0,0 +1,51 @@
+// SPDX-FileCopyrightText: © 2023 Merqury Cybersecurity Ltd <info@merqury.eu>
+// SPDX-License-Identifier: PolyForm-Noncommercial-1.0.0
+
+//! Lenient deserialization of the integer fields of the models. The standard
+//! defines them as 32-bit integers, but they are deserialized as 64-bit
+//! integers, also accepting floats without a fractional part, so that a reply
+//! using a wider type can still be read and its fields reported individually
+//! by the OpenAPI validation instead of failing as a whole.
+
+use serde::{
+    de::{self, Unexpected, Visitor},
+    Deserializer,
+};
+use std::fmt;
+
+struct IntegerVisitor;
+
+impl<'de> Visitor<'de> for IntegerVisitor {
+    type Value = i64;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("an integer")
+    }
+
+    fn visit_i64<E: de::Error>(self, val: i64) -> Result<i64, E> {
+        Ok(val)
+    }
+
+    fn visit_u64<E: de::Error>(self, val: u64) -> Result<i64, E> {
+        i64::try_from(val).map_err(|_| {
+            E::invalid_value(Unexpected::Unsigned(val), &"a 64-bit integer")
+        })
+    }
+
+    fn visit_f64<E: de::Error>(self, val: f64) -> Result<i64, E> {
+        // The bounds are exclusive, as `i64::MAX` is not exactly
+        // representable as a float.
+        if val.fract() == 0.0 && val >= i64::MIN as f64 && val < i64::MAX as f64
+        {
+            Ok(val as i64)
+        } else {
+            Err(E::invalid_value(Unexpected::Float(val), &self))
+        }
+    }
+}
+
+pub fn deserialize<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<i64, D::Error> {
+    deserializer.deserialize_any(IntegerVisitor)
+}
@@ -1,7 +1,9 @@
 // SPDX-FileCopyrightText: © 2023 Merqury Cybersecurity Ltd <info@merqury.eu>
 // SPDX-License-Identifier: PolyForm-Noncommercial-1.0.0
 
+use crate::common::redaction;
 use serde::{Deserialize, Serialize};
+use std::fmt;
 
 #[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone)]
 pub struct KeyContainer {
@@ -9,10 +11,20 @@ pub struct KeyContainer {
     pub keys: Vec<KeyContainerElement>,
 }
 
-#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone)]
+#[derive(Serialize, Deserialize, PartialEq, Eq, Clone)]
 pub struct KeyContainerElement {
     #[serde(rename = "key_ID")]
     pub key_id: uuid::Uuid,
     #[serde(skip_serializing)]
     pub key: Option<String>,
 }
+
+// The key material is redacted, as keys are printed by failing assertions.
+impl fmt::Debug for KeyContainerElement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("KeyContainerElement")
+            .field("key_id", &self.key_id)
+            .field("key", &self.key.as_deref().map(redaction::redact))
+            .finish()
+    }
+}
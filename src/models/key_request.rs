@@ -0,0 +1,24 @@
+// SPDX-FileCopyrightText: © 2023 Merqury Cybersecurity Ltd <info@merqury.eu>
+// SPDX-License-Identifier: PolyForm-Noncommercial-1.0.0
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+/// Body of a `POST` request to `enc_keys`. The integer fields are as wide as
+/// those of [`Status`](super::status::Status), from which they are derived.
+#[derive(Debug, Default, Serialize, Deserialize, PartialEq, Clone)]
+pub struct KeyRequest {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub number: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub size: Option<i64>,
+    #[serde(
+        rename = "additional_slave_SAE_IDs",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub additional_slave_sae_ids: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub extension_mandatory: Option<Vec<Map<String, Value>>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub extension_optional: Option<Vec<Map<String, Value>>>,
+}
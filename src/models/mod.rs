@@ -2,5 +2,7 @@
 // SPDX-License-Identifier: PolyForm-Noncommercial-1.0.0
 
 pub mod error_message;
+pub mod integer;
 pub mod key;
+pub mod key_request;
 pub mod status;
@@ -0,0 +1,36 @@
+// SPDX-FileCopyrightText: © 2023 Merqury Cybersecurity Ltd <info@merqury.eu>
+// SPDX-License-Identifier: PolyForm-Noncommercial-1.0.0
+
+use super::integer;
+use serde::{Deserialize, Serialize};
+
+/// Status of the link between the KMEs of two SAEs. The integer fields are
+/// wider than the 32 bits of the standard, see [`integer`].
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone)]
+pub struct Status {
+    #[serde(rename = "source_KME_ID")]
+    pub source_kme_id: String,
+    #[serde(rename = "target_KME_ID")]
+    pub target_kme_id: String,
+    #[serde(rename = "master_SAE_ID")]
+    pub master_sae_id: String,
+    #[serde(rename = "slave_SAE_ID")]
+    pub slave_sae_id: String,
+    #[serde(deserialize_with = "integer::deserialize")]
+    pub key_size: i64,
+    #[serde(deserialize_with = "integer::deserialize")]
+    pub stored_key_count: i64,
+    #[serde(deserialize_with = "integer::deserialize")]
+    pub max_key_count: i64,
+    #[serde(deserialize_with = "integer::deserialize")]
+    pub max_key_per_request: i64,
+    #[serde(deserialize_with = "integer::deserialize")]
+    pub max_key_size: i64,
+    #[serde(deserialize_with = "integer::deserialize")]
+    pub min_key_size: i64,
+    #[serde(
+        rename = "max_SAE_ID_count",
+        deserialize_with = "integer::deserialize"
+    )]
+    pub max_sae_id_count: i64,
+}
@@ -0,0 +1,459 @@
+// SPDX-FileCopyrightText: © 2023 Merqury Cybersecurity Ltd <info@merqury.eu>
+// SPDX-License-Identifier: PolyForm-Noncommercial-1.0.0
+
+use crate::metrics;
+use clap::{value_parser, ValueEnum};
+use etsi_gs_qkd_014_testsuite::{
+    common::{
+        self,
+        client::AsyncClient,
+        config::{Side, CONFIG},
+        error::Error,
+        key_material,
+    },
+    models::{
+        key::{KeyContainer, KeyContainerElement},
+        key_request::KeyRequest,
+        status::Status,
+    },
+};
+use reqwest::Method;
+use serde::Serialize;
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+/// Minimum number of samples needed before a trend is looked for, so that the
+/// first and last quarters of the run hold at least 3 samples each.
+static MIN_TREND_SAMPLES: usize = 12;
+/// Relative change, between the medians of the first and last quarters of the
+/// run, beyond which a metric is considered to have degraded.
+static DEGRADATION_THRESHOLD: f64 = 0.2;
+
+#[derive(clap::Args)]
+pub struct Args {
+    /// Duration of the soak test, in seconds.
+    #[arg(
+        long,
+        default_value_t = 4 * 60 * 60,
+        value_parser = value_parser!(u64).range(1..)
+    )]
+    duration: u64,
+    /// Interval between samples, in seconds.
+    #[arg(
+        long,
+        default_value_t = 60,
+        value_parser = value_parser!(u64).range(1..)
+    )]
+    interval: u64,
+    /// Path of the file to which the samples are written. Samples are
+    /// written as soon as they are taken.
+    #[arg(long)]
+    output: PathBuf,
+    /// Format of the output file. In JSON format, each sample is written as
+    /// a JSON object on its own line.
+    #[arg(long, value_enum, default_value_t = Format::Csv)]
+    format: Format,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Format {
+    Csv,
+    Json,
+}
+
+#[derive(Serialize)]
+struct Sample {
+    timestamp: u64,
+    elapsed_secs: f64,
+    iterations: usize,
+    failed_iterations: usize,
+    error_rate: f64,
+    latency_p50_ms: f64,
+    latency_p95_ms: f64,
+    latency_p99_ms: f64,
+    stored_key_count: Option<i64>,
+    /// Number of keys delivered to the soak test since it started, which are
+    /// no longer stored by the KME.
+    keys_consumed: usize,
+}
+
+#[derive(Default)]
+struct Window {
+    latencies: Vec<Duration>,
+    failed_iterations: usize,
+}
+
+struct SampleWriter {
+    writer: BufWriter<File>,
+    format: Format,
+    path: PathBuf,
+}
+
+pub async fn run(args: Args) -> Result<(), Error> {
+    let master_client =
+        common::build_async_client(CONFIG.master(), Side::Master)?;
+    let slave_client = common::build_async_client(CONFIG.slave(), Side::Slave)?;
+    let mut writer = SampleWriter::new(&args.output, args.format)?;
+    let mut methods = [Method::GET, Method::POST].into_iter().cycle();
+    let mut window = Window::default();
+    let mut samples = Vec::new();
+    let mut errors: BTreeMap<String, usize> = BTreeMap::new();
+    let mut keys_consumed = 0;
+    let interval = Duration::from_secs(args.interval);
+    let start = Instant::now();
+    let deadline = start + Duration::from_secs(args.duration);
+    let mut next_sample = start + interval;
+
+    while Instant::now() < deadline {
+        let iteration_start = Instant::now();
+        let result = request_and_retrieve_key(
+            &master_client,
+            &slave_client,
+            methods.next().unwrap(),
+            &mut keys_consumed,
+        )
+        .await;
+
+        window.latencies.push(iteration_start.elapsed());
+
+        if let Err(label) = result {
+            window.failed_iterations += 1;
+            *errors.entry(label).or_default() += 1;
+        }
+
+        if Instant::now() >= next_sample {
+            let sample = take_sample(
+                &master_client,
+                std::mem::take(&mut window),
+                start,
+                keys_consumed,
+                &mut errors,
+            )
+            .await;
+
+            writer.write(&sample)?;
+            samples.push(sample);
+            next_sample += interval;
+        }
+    }
+
+    if !window.latencies.is_empty() {
+        let sample = take_sample(
+            &master_client,
+            window,
+            start,
+            keys_consumed,
+            &mut errors,
+        )
+        .await;
+
+        writer.write(&sample)?;
+        samples.push(sample);
+    }
+
+    let iterations: usize = samples.iter().map(|val| val.iterations).sum();
+    let failed_iterations: usize =
+        samples.iter().map(|val| val.failed_iterations).sum();
+
+    println!(
+        "Spec version: {}, iterations: {} ({} failed), samples: {}",
+        CONFIG.spec_version,
+        iterations,
+        failed_iterations,
+        samples.len()
+    );
+
+    for (error, count) in &errors {
+        println!("  error: {} x {}", error, count);
+    }
+
+    let degradations = find_degradations(&samples);
+
+    for degradation in &degradations {
+        println!("DEGRADATION: {}", degradation);
+    }
+
+    if !degradations.is_empty() {
+        std::process::exit(1);
+    }
+
+    Ok(())
+}
+
+/// Requests a single key as the master SAE and retrieves it by its ID as the
+/// slave SAE, mirroring the `successful_key_request_and_retrieval` test.
+/// `keys_consumed` is increased by the number of keys delivered.
+async fn request_and_retrieve_key(
+    master_client: &AsyncClient,
+    slave_client: &AsyncClient,
+    request_method: Method,
+    keys_consumed: &mut usize,
+) -> Result<(), String> {
+    let enc_keys_url =
+        format!("{}/{}/enc_keys", CONFIG.base_url, CONFIG.slave().id);
+    let dec_keys_url =
+        format!("{}/{}/dec_keys", CONFIG.slave_base_url, CONFIG.master().id);
+
+    let enc_keys_request = match request_method {
+        Method::GET => master_client
+            .request(request_method.clone(), enc_keys_url)
+            .query(&[("number", 1)]),
+        Method::POST => master_client
+            .request(request_method.clone(), enc_keys_url)
+            .json(&KeyRequest {
+                number: Some(1),
+                ..Default::default()
+            }),
+        _ => {
+            panic!("Only 'GET' and 'POST' methods are supported")
+        }
+    };
+    let returned_keys = metrics::send_key_request(enc_keys_request).await?;
+
+    *keys_consumed += returned_keys.keys.len();
+
+    if returned_keys.keys.len() != 1 {
+        return Err("unexpected number of keys".to_string());
+    }
+
+    let dec_keys_request = match request_method {
+        Method::GET => slave_client
+            .request(request_method, dec_keys_url)
+            .query(&[("key_ID", returned_keys.keys[0].key_id)]),
+        Method::POST => slave_client
+            .request(request_method, dec_keys_url)
+            .json(&returned_keys),
+        _ => {
+            panic!("Only 'GET' and 'POST' methods are supported")
+        }
+    };
+    let retrieved_keys = metrics::send_key_request(dec_keys_request).await?;
+
+    if !same_keys(&returned_keys, &retrieved_keys) {
+        return Err("retrieved key does not match".to_string());
+    }
+
+    Ok(())
+}
+
+/// Whether both containers hold the same keys, with the same key material
+/// once decoded, as the KMEs may encode it differently.
+fn same_keys(
+    returned_keys: &KeyContainer,
+    retrieved_keys: &KeyContainer,
+) -> bool {
+    let decode = |key: &KeyContainerElement| {
+        key_material::decode(key.key.as_deref()?).ok().map(|(val, _)| val)
+    };
+
+    returned_keys.keys.len() == retrieved_keys.keys.len()
+        && returned_keys.keys.iter().zip(&retrieved_keys.keys).all(
+            |(returned, retrieved)| {
+                returned.key_id == retrieved.key_id
+                    && matches!(
+                        (decode(returned), decode(retrieved)),
+                        (Some(returned_bytes), Some(retrieved_bytes))
+                            if returned_bytes == retrieved_bytes
+                    )
+            },
+        )
+}
+
+/// Takes a sample of the iterations in the window. A failure to fetch the
+/// stored key count is counted among the errors.
+async fn take_sample(
+    client: &AsyncClient,
+    mut window: Window,
+    start: Instant,
+    keys_consumed: usize,
+    errors: &mut BTreeMap<String, usize>,
+) -> Sample {
+    window.latencies.sort();
+
+    let stored_key_count = match fetch_stored_key_count(client).await {
+        Ok(val) => Some(val),
+        Err(label) => {
+            eprintln!("Failed to fetch the stored key count: {}", label);
+            *errors.entry(format!("status: {}", label)).or_default() += 1;
+
+            None
+        }
+    };
+
+    let iterations = window.latencies.len();
+
+    Sample {
+        timestamp: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs(),
+        elapsed_secs: start.elapsed().as_secs_f64(),
+        iterations,
+        failed_iterations: window.failed_iterations,
+        error_rate: window.failed_iterations as f64 / iterations.max(1) as f64,
+        latency_p50_ms: metrics::percentile(&window.latencies, 50.0),
+        latency_p95_ms: metrics::percentile(&window.latencies, 95.0),
+        latency_p99_ms: metrics::percentile(&window.latencies, 99.0),
+        stored_key_count,
+        keys_consumed,
+    }
+}
+
+/// Fetches the stored key count reported by `/status`. Failures are mapped
+/// to a short label, as for key requests.
+async fn fetch_stored_key_count(client: &AsyncClient) -> Result<i64, String> {
+    let status_url =
+        format!("{}/{}/status", CONFIG.base_url, CONFIG.slave().id);
+    let response = client
+        .get(&status_url)
+        .send()
+        .await
+        .map_err(|e| metrics::error_label(&e))?;
+
+    if !response.status().is_success() {
+        return Err(response.status().to_string());
+    }
+
+    response
+        .json::<Status>()
+        .map(|status| status.stored_key_count)
+        .map_err(|_| "invalid response body".to_string())
+}
+
+/// Returns a description of every metric whose median over the last quarter
+/// of the samples is worse than over the first quarter, by more than both
+/// [`DEGRADATION_THRESHOLD`] and the smallest change that matters for the
+/// metric. Medians keep isolated spikes from being mistaken for a trend.
+fn find_degradations(samples: &[Sample]) -> Vec<String> {
+    let mut degradations = Vec::new();
+    // Name, values, whether higher is worse, and the smallest change that
+    // matters.
+    let metrics: [(&str, Vec<f64>, bool, f64); 3] = [
+        (
+            "p95 latency (ms)",
+            samples.iter().map(|val| val.latency_p95_ms).collect(),
+            true,
+            1.0,
+        ),
+        (
+            "error rate",
+            samples.iter().map(|val| val.error_rate).collect(),
+            true,
+            0.01,
+        ),
+        (
+            // The keys consumed by the soak test itself are added back, so that
+            // only keys lost otherwise are reported.
+            "stored key count, excluding the keys consumed",
+            samples
+                .iter()
+                .filter_map(|val| {
+                    val.stored_key_count
+                        .map(|count| (count + val.keys_consumed as i64) as f64)
+                })
+                .collect(),
+            false,
+            1.0,
+        ),
+    ];
+
+    for (name, values, higher_is_worse, min_change) in metrics {
+        if values.len() < MIN_TREND_SAMPLES {
+            continue;
+        }
+
+        let quarter = values.len() / 4;
+        let first = median(&values[..quarter]);
+        let last = median(&values[values.len() - quarter..]);
+        let change = match higher_is_worse {
+            true => last - first,
+            false => first - last,
+        };
+
+        if change > min_change && change > first.abs() * DEGRADATION_THRESHOLD {
+            degradations.push(format!(
+                "{} went from a median of {} to {} between the first and \
+                 last {} samples",
+                name, first, last, quarter
+            ));
+        }
+    }
+
+    degradations
+}
+
+fn median(values: &[f64]) -> f64 {
+    let mut sorted = values.to_vec();
+
+    sorted.sort_by(f64::total_cmp);
+
+    match sorted.len() % 2 {
+        0 => (sorted[sorted.len() / 2 - 1] + sorted[sorted.len() / 2]) / 2.0,
+        _ => sorted[sorted.len() / 2],
+    }
+}
+
+impl SampleWriter {
+    fn new(path: &Path, format: Format) -> Result<Self, Error> {
+        let file = File::create(path).map_err(|e| output_error(path, e))?;
+        let mut sample_writer = Self {
+            writer: BufWriter::new(file),
+            format,
+            path: path.to_path_buf(),
+        };
+
+        if let Format::Csv = format {
+            sample_writer.write_line(
+                "timestamp,elapsed_secs,iterations,failed_iterations,\
+                 error_rate,latency_p50_ms,latency_p95_ms,latency_p99_ms,\
+                 stored_key_count,keys_consumed",
+            )?;
+        }
+
+        Ok(sample_writer)
+    }
+
+    fn write(&mut self, sample: &Sample) -> Result<(), Error> {
+        let line = match self.format {
+            Format::Csv => format!(
+                "{},{:.3},{},{},{:.6},{:.3},{:.3},{:.3},{},{}",
+                sample.timestamp,
+                sample.elapsed_secs,
+                sample.iterations,
+                sample.failed_iterations,
+                sample.error_rate,
+                sample.latency_p50_ms,
+                sample.latency_p95_ms,
+                sample.latency_p99_ms,
+                sample
+                    .stored_key_count
+                    .map_or(String::new(), |val| val.to_string()),
+                sample.keys_consumed
+            ),
+            Format::Json => serde_json::to_string(sample).unwrap(),
+        };
+
+        self.write_line(&line)
+    }
+
+    /// Writes and flushes the given line, so that the samples taken so far
+    /// are kept even if the run is interrupted.
+    fn write_line(&mut self, line: &str) -> Result<(), Error> {
+        writeln!(self.writer, "{}", line)
+            .and_then(|_| self.writer.flush())
+            .map_err(|e| output_error(&self.path, e))
+    }
+}
+
+fn output_error(path: &Path, e: io::Error) -> Error {
+    Error::Config(format!(
+        "cannot write the samples to '{}': {}",
+        path.display(),
+        e
+    ))
+}
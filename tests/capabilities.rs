@@ -0,0 +1,95 @@
+// SPDX-FileCopyrightText: © 2023 Merqury Cybersecurity Ltd <info@merqury.eu>
+// SPDX-License-Identifier: PolyForm-Noncommercial-1.0.0
+
+//! Tests of the key sizes and counts derived from the capabilities of a KME,
+//! given as a `Status` fixture rather than discovered, so no KME is needed.
+
+mod common;
+
+use etsi_gs_qkd_014_testsuite::{
+    common::capabilities::Capabilities, models::status::Status,
+};
+use pretty_assertions::assert_eq;
+use rstest::rstest;
+
+fn capabilities(
+    min_key_size: i64,
+    max_key_size: i64,
+    max_key_per_request: i64,
+) -> Capabilities {
+    let status = Status {
+        key_size: min_key_size,
+        max_key_per_request,
+        max_key_size,
+        min_key_size,
+        ..common::status()
+    };
+
+    Capabilities::new(status, 7)
+}
+
+#[rstest]
+#[case::wide_range(64, 1024)]
+#[case::narrow_range(64, 72)]
+#[case::single_size(256, 256)]
+#[case::unaligned_bounds(60, 100)]
+fn key_sizes_are_within_range(#[case] min: i64, #[case] max: i64) {
+    let sizes = capabilities(min, max, 1).key_sizes();
+
+    assert_eq!(sizes.first(), Some(&min));
+    assert_eq!(sizes.last(), Some(&max));
+
+    for size in &sizes {
+        assert!(*size >= min && *size <= max);
+        assert!(size % 8 == 0 || *size == min || *size == max);
+    }
+}
+
+#[rstest]
+#[case::aligned_bounds(64, 96, 8, vec![64, 72, 80, 88, 96])]
+#[case::unaligned_bounds(60, 100, 8, vec![64, 72, 80, 88, 96])]
+#[case::wide_stride(64, 1024, 256, vec![64, 320, 576, 832])]
+fn key_size_sweep(
+    #[case] min: i64,
+    #[case] max: i64,
+    #[case] stride: usize,
+    #[case] expected: Vec<i64>,
+) {
+    assert_eq!(capabilities(min, max, 1).key_size_sweep(stride), expected);
+}
+
+#[rstest]
+#[case::single_key(1, vec![1])]
+#[case::several_keys(128, vec![1, 128])]
+fn key_counts_include_bounds(#[case] max: i64, #[case] bounds: Vec<i64>) {
+    let counts = capabilities(64, 1024, max).key_counts();
+
+    for bound in bounds {
+        assert!(counts.contains(&bound));
+    }
+
+    assert!(counts.iter().all(|count| (1..=max).contains(count)));
+}
+
+#[rstest]
+#[case::supported(5, 1024, None)]
+#[case::too_many_keys(6, 1024, Some("6 keys requested"))]
+#[case::too_large(1, 1032, Some("1032-bit keys requested"))]
+#[case::too_small(1, 56, Some("56-bit keys requested"))]
+#[case::unaligned(1, 100, Some("not a multiple of 8"))]
+fn unsupported_reason(
+    #[case] number: i64,
+    #[case] size: i64,
+    #[case] expected: Option<&str>,
+) {
+    let reason = capabilities(64, 1024, 5).unsupported_reason(number, size);
+
+    match expected {
+        Some(val) => assert!(
+            reason.as_deref().unwrap_or_default().contains(val),
+            "Unexpected reason: {:?}",
+            reason
+        ),
+        None => assert_eq!(reason, None),
+    }
+}
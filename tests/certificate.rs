@@ -0,0 +1,375 @@
+// SPDX-FileCopyrightText: © 2023 Merqury Cybersecurity Ltd <info@merqury.eu>
+// SPDX-License-Identifier: PolyForm-Noncommercial-1.0.0
+
+//! Tests of the reading of SAE identities in their supported formats, and of
+//! the inspection of certificates, as done by the `doctor` command. The
+//! certificates and keys are generated by the tests themselves, which run
+//! offline.
+
+use etsi_gs_qkd_014_testsuite::common::{
+    self,
+    certificate::{Certificate, Identity},
+    config::SaeIdentity,
+    error::Error,
+};
+use openssl::{
+    asn1::Asn1Time,
+    ec::{EcGroup, EcKey},
+    hash::MessageDigest,
+    nid::Nid,
+    pkcs12::Pkcs12,
+    pkey::{PKey, Private},
+    rsa::Rsa,
+    symm::Cipher,
+    x509::{extension::SubjectAlternativeName, X509NameBuilder, X509},
+};
+use pretty_assertions::assert_eq;
+use rstest::rstest;
+use std::{env, fs};
+
+static DAY: i64 = 24 * 60 * 60;
+
+static PASSWORD: &str = "secret";
+
+fn ec_key() -> PKey<Private> {
+    let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1).unwrap();
+
+    PKey::from_ec_key(EcKey::generate(&group).unwrap()).unwrap()
+}
+
+/// Builds a self-signed certificate, valid between the given offsets from now,
+/// in seconds.
+fn x509(common_name: &str, alt_names: &[&str], from: i64, until: i64) -> X509 {
+    signed_x509(&ec_key(), common_name, alt_names, from, until)
+}
+
+fn signed_x509(
+    key: &PKey<Private>,
+    common_name: &str,
+    alt_names: &[&str],
+    from: i64,
+    until: i64,
+) -> X509 {
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs() as i64;
+    let mut name = X509NameBuilder::new().unwrap();
+
+    name.append_entry_by_text("O", "Example").unwrap();
+    name.append_entry_by_text("CN", common_name).unwrap();
+
+    let name = name.build();
+    let mut builder = X509::builder().unwrap();
+
+    builder.set_version(2).unwrap();
+    builder.set_subject_name(&name).unwrap();
+    builder.set_issuer_name(&name).unwrap();
+    builder.set_pubkey(key).unwrap();
+    builder.set_not_before(&Asn1Time::from_unix(now + from).unwrap()).unwrap();
+    builder.set_not_after(&Asn1Time::from_unix(now + until).unwrap()).unwrap();
+
+    if !alt_names.is_empty() {
+        let mut extension = SubjectAlternativeName::new();
+
+        for alt_name in alt_names {
+            extension.dns(alt_name);
+        }
+
+        let extension =
+            extension.build(&builder.x509v3_context(None, None)).unwrap();
+
+        builder.append_extension(extension).unwrap();
+    }
+
+    builder.sign(key, MessageDigest::sha256()).unwrap();
+    builder.build()
+}
+
+#[test]
+fn describes_certificate() {
+    let certificate = Certificate::new(&x509(
+        "sae_001",
+        &["sae-001.example"],
+        -DAY,
+        90 * DAY,
+    ))
+    .unwrap();
+
+    assert_eq!(certificate.subject, "O=Example, CN=sae_001");
+    assert_eq!(certificate.common_name.as_deref(), Some("sae_001"));
+    assert_eq!(certificate.alt_names, vec!["sae-001.example"]);
+    assert!(
+        (89..=90).contains(&certificate.days_left),
+        "{}",
+        certificate.days_left
+    );
+    assert!(!certificate.expired);
+    assert!(!certificate.not_yet_valid);
+}
+
+#[rstest]
+#[case::expired(-2 * DAY, -60, true, false)]
+#[case::not_yet_valid(60, 2 * DAY, false, true)]
+#[case::valid(-60, 60, false, false)]
+fn validity(
+    #[case] from: i64,
+    #[case] until: i64,
+    #[case] expired: bool,
+    #[case] not_yet_valid: bool,
+) {
+    let certificate =
+        Certificate::new(&x509("sae_001", &[], from, until)).unwrap();
+
+    assert_eq!(
+        (certificate.expired, certificate.not_yet_valid),
+        (expired, not_yet_valid)
+    );
+}
+
+#[rstest]
+#[case::common_name("sae_001", &[], true)]
+#[case::alt_name("kme-client", &["sae_001"], true)]
+#[case::other_sae("sae_002", &["sae-002.example"], false)]
+fn names_sae(
+    #[case] common_name: &str,
+    #[case] alt_names: &[&str],
+    #[case] expected: bool,
+) {
+    let certificate =
+        Certificate::new(&x509(common_name, alt_names, -DAY, DAY)).unwrap();
+
+    assert_eq!(certificate.names_sae("sae_001"), expected);
+}
+
+#[test]
+fn reads_first_certificate_of_chain() {
+    let path = env::temp_dir().join("etsi_014_test_suite_chain.pem");
+    let mut pem = x509("sae_001", &[], -DAY, DAY).to_pem().unwrap();
+
+    pem.extend(x509("root", &[], -DAY, DAY).to_pem().unwrap());
+    fs::write(&path, pem).unwrap();
+
+    let certificate = Certificate::read(path.to_str().unwrap()).unwrap();
+
+    assert_eq!(certificate.common_name.as_deref(), Some("sae_001"));
+}
+
+#[rstest]
+#[case::missing(None, "cannot be read")]
+#[case::not_pem(Some("not a certificate"), "holds no certificate")]
+fn unreadable_certificate(
+    #[case] content: Option<&str>,
+    #[case] expected_reason: &str,
+) {
+    let path = env::temp_dir().join(format!(
+        "etsi_014_test_suite_unreadable_{}.pem",
+        content.is_some()
+    ));
+    let _ = fs::remove_file(&path);
+
+    if let Some(content) = content {
+        fs::write(&path, content).unwrap();
+    }
+
+    let error = Certificate::read(path.to_str().unwrap()).err().unwrap();
+
+    assert!(
+        matches!(
+            &error,
+            Error::Certificate { reason, .. }
+                if reason.starts_with(expected_reason)
+        ),
+        "{}",
+        error
+    );
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Format {
+    /// Certificate and traditional RSA key in a single PEM file.
+    CombinedPem,
+    /// PEM certificate and PKCS#8 key files.
+    SeparatePem,
+    /// PEM certificate and encrypted PKCS#8 key files.
+    EncryptedPkcs8,
+    /// PEM certificate and traditional encrypted RSA key files.
+    EncryptedRsa,
+    /// DER certificate and key files.
+    SeparateDer,
+    /// Password-protected PKCS#12 bundle, with a CA certificate.
+    Pkcs12,
+}
+
+/// Writes an identity for `sae_001` in the given format, returning it with
+/// the password set.
+fn write_identity(format: Format) -> SaeIdentity {
+    let key = match format {
+        Format::CombinedPem | Format::EncryptedRsa => {
+            PKey::from_rsa(Rsa::generate(2048).unwrap()).unwrap()
+        }
+        _ => ec_key(),
+    };
+    let certificate = signed_x509(&key, "sae_001", &[], -DAY, DAY);
+    let dir = env::temp_dir();
+    let path = |extension: &str| {
+        dir.join(format!("etsi_014_test_suite_{:?}.{}", format, extension))
+            .to_str()
+            .unwrap()
+            .to_string()
+    };
+    let (crt, key_path) = (path("crt"), path("key"));
+    let cipher = Cipher::aes_256_cbc();
+    let rsa_key = || key.rsa().unwrap();
+    let (crt_content, key_content) = match format {
+        Format::CombinedPem => {
+            let mut pem = certificate.to_pem().unwrap();
+
+            pem.extend(rsa_key().private_key_to_pem().unwrap());
+            (pem, None)
+        }
+        Format::SeparatePem => (
+            certificate.to_pem().unwrap(),
+            Some(key.private_key_to_pem_pkcs8().unwrap()),
+        ),
+        Format::EncryptedPkcs8 => (
+            certificate.to_pem().unwrap(),
+            Some(
+                key.private_key_to_pem_pkcs8_passphrase(
+                    cipher,
+                    PASSWORD.as_bytes(),
+                )
+                .unwrap(),
+            ),
+        ),
+        Format::EncryptedRsa => (
+            certificate.to_pem().unwrap(),
+            Some(
+                rsa_key()
+                    .private_key_to_pem_passphrase(cipher, PASSWORD.as_bytes())
+                    .unwrap(),
+            ),
+        ),
+        Format::SeparateDer => (
+            certificate.to_der().unwrap(),
+            Some(key.private_key_to_der().unwrap()),
+        ),
+        Format::Pkcs12 => {
+            let mut ca = openssl::stack::Stack::new().unwrap();
+
+            ca.push(x509("root", &[], -DAY, DAY)).unwrap();
+
+            let mut builder = Pkcs12::builder();
+
+            builder.name("sae_001").pkey(&key).cert(&certificate).ca(ca);
+
+            (builder.build2(PASSWORD).unwrap().to_der().unwrap(), None)
+        }
+    };
+
+    fs::write(&crt, crt_content).unwrap();
+
+    if let Some(content) = &key_content {
+        fs::write(&key_path, content).unwrap();
+    }
+
+    SaeIdentity {
+        id: "sae_001".to_string(),
+        crt,
+        key: key_content.map(|_| key_path),
+        password: Some(PASSWORD.to_string()),
+    }
+}
+
+#[rstest]
+#[case::combined_pem(Format::CombinedPem, 1)]
+#[case::separate_pem(Format::SeparatePem, 1)]
+#[case::encrypted_pkcs8(Format::EncryptedPkcs8, 1)]
+#[case::encrypted_rsa(Format::EncryptedRsa, 1)]
+#[case::separate_der(Format::SeparateDer, 1)]
+#[case::pkcs12(Format::Pkcs12, 2)]
+fn reads_identity(#[case] format: Format, #[case] chain_length: usize) {
+    let sae = write_identity(format);
+    let identity = Identity::read(&sae).unwrap();
+
+    assert_eq!(identity.chain.len(), chain_length);
+    assert_eq!(
+        identity.certificate().unwrap().common_name.as_deref(),
+        Some("sae_001")
+    );
+    assert!(
+        common::load_identity(&sae).is_ok(),
+        "Not accepted by the client"
+    );
+}
+
+#[rstest]
+#[case::encrypted_pkcs8_without_password(
+    Format::EncryptedPkcs8,
+    None,
+    "the private key is encrypted, but no password is set"
+)]
+#[case::encrypted_pkcs8_wrong_password(
+    Format::EncryptedPkcs8,
+    Some("wrong"),
+    "cannot decrypt the private key"
+)]
+#[case::encrypted_rsa_without_password(
+    Format::EncryptedRsa,
+    None,
+    "the private key is encrypted, but no password is set"
+)]
+#[case::pkcs12_wrong_password(
+    Format::Pkcs12,
+    Some("wrong"),
+    "cannot decrypt the PKCS#12 bundle"
+)]
+fn wrong_password(
+    #[case] format: Format,
+    #[case] password: Option<&str>,
+    #[case] expected_reason: &str,
+) {
+    let sae = SaeIdentity {
+        password: password.map(String::from),
+        ..write_identity(format)
+    };
+    let error = Identity::read(&sae).err().unwrap();
+
+    assert!(
+        matches!(
+            &error,
+            Error::Certificate { reason, .. }
+                if reason.starts_with(expected_reason)
+        ),
+        "{}",
+        error
+    );
+}
+
+#[test]
+fn mismatched_private_key() {
+    let sae = write_identity(Format::SeparatePem);
+    let other_key = env::temp_dir().join("etsi_014_test_suite_other.key");
+
+    fs::write(&other_key, ec_key().private_key_to_pem_pkcs8().unwrap())
+        .unwrap();
+
+    let sae = SaeIdentity {
+        key: other_key.to_str().map(String::from),
+        ..sae
+    };
+    let error = Identity::read(&sae).err().unwrap();
+
+    assert!(
+        error.reason().contains("does not match the certificate"),
+        "{}",
+        error
+    );
+}
+
+#[test]
+fn password_is_not_printed() {
+    let sae = write_identity(Format::Pkcs12);
+
+    assert!(!format!("{:?}", sae).contains(PASSWORD));
+}
@@ -1,33 +1,24 @@
 // SPDX-FileCopyrightText: © 2023 Merqury Cybersecurity Ltd <info@merqury.eu>
 // SPDX-License-Identifier: PolyForm-Noncommercial-1.0.0
 
-pub mod config;
-
-use config::CONFIG;
-use std::{fs::File, io::Read};
-
-pub fn build_client(client_pem_path: &str) -> reqwest::blocking::Client {
-    reqwest::blocking::Client::builder()
-        .add_root_certificate(load_root_certificate())
-        .identity(load_identity(client_pem_path))
-        .min_tls_version(reqwest::tls::Version::TLS_1_3)
-        .danger_accept_invalid_certs(false)
-        .use_rustls_tls()
-        .build()
-        .unwrap()
-}
-
-fn load_root_certificate() -> reqwest::Certificate {
-    let mut cert_buf = Vec::new();
-
-    File::open(&CONFIG.root_crt).unwrap().read_to_end(&mut cert_buf).unwrap();
-
-    reqwest::Certificate::from_pem(&cert_buf).unwrap()
-}
-
-fn load_identity(pem_path: &str) -> reqwest::Identity {
-    let mut buf = Vec::new();
-    File::open(pem_path).unwrap().read_to_end(&mut buf).unwrap();
-
-    reqwest::Identity::from_pem(&buf).unwrap()
+//! Fixtures shared by the tests that do not talk to a KME.
+
+use etsi_gs_qkd_014_testsuite::models::status::Status;
+
+/// A `Status` reply satisfying every rule of the standard, for tests to
+/// override the fields they are about.
+pub fn status() -> Status {
+    Status {
+        source_kme_id: "kme_1".to_string(),
+        target_kme_id: "kme_2".to_string(),
+        master_sae_id: "sae_1".to_string(),
+        slave_sae_id: "sae_2".to_string(),
+        key_size: 256,
+        stored_key_count: 10,
+        max_key_count: 1000,
+        max_key_per_request: 16,
+        max_key_size: 1024,
+        min_key_size: 64,
+        max_sae_id_count: 0,
+    }
 }
@@ -0,0 +1,26 @@
+// SPDX-FileCopyrightText: © 2023 Merqury Cybersecurity Ltd <info@merqury.eu>
+// SPDX-License-Identifier: PolyForm-Noncommercial-1.0.0
+
+//! Tests of how the race tests tell whether their requests overlapped, using
+//! made-up send and receive times instead of a KME.
+
+use etsi_gs_qkd_014_testsuite::common::concurrency;
+use rstest::rstest;
+use std::time::{Duration, Instant};
+
+#[rstest]
+#[case::none(vec![], 0)]
+#[case::single(vec![(0, 10)], 1)]
+#[case::one_after_another(vec![(0, 10), (10, 20), (20, 30)], 1)]
+#[case::overlapping_pair(vec![(0, 10), (5, 15), (20, 30)], 2)]
+#[case::all_together(vec![(0, 30), (1, 29), (2, 28)], 3)]
+fn max_in_flight(#[case] exchanges: Vec<(u64, u64)>, #[case] expected: usize) {
+    let start = Instant::now();
+    let at = |millis: u64| start + Duration::from_millis(millis);
+    let exchanges: Vec<(Instant, Instant)> = exchanges
+        .into_iter()
+        .map(|(sent, received)| (at(sent), at(received)))
+        .collect();
+
+    assert_eq!(concurrency::max_in_flight(&exchanges), expected);
+}
@@ -0,0 +1,110 @@
+// SPDX-FileCopyrightText: © 2023 Merqury Cybersecurity Ltd <info@merqury.eu>
+// SPDX-License-Identifier: PolyForm-Noncommercial-1.0.0
+
+//! Tests of the classification of the errors of the test suite, provoked
+//! against local sockets that refuse connections or do not speak TLS rather
+//! than against a KME.
+
+use etsi_gs_qkd_014_testsuite::common::{
+    self, config::SaeIdentity, error::Error,
+};
+use pretty_assertions::assert_eq;
+use std::{
+    env, fs,
+    io::{Read, Write},
+    net::TcpListener,
+    thread,
+};
+
+fn send(url: &str) -> Error {
+    let client =
+        reqwest::blocking::Client::builder().use_rustls_tls().build().unwrap();
+
+    Error::from_reqwest(&client.get(url).send().err().unwrap())
+}
+
+fn sae(crt: &str) -> SaeIdentity {
+    SaeIdentity {
+        id: "sae_1".to_string(),
+        crt: crt.to_string(),
+        key: None,
+        password: None,
+    }
+}
+
+#[test]
+fn connection_refused() {
+    let port =
+        TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+    let url = format!("https://127.0.0.1:{}/", port);
+
+    assert_eq!(
+        send(&url),
+        Error::Transport {
+            url: url.clone(),
+            reason: "connection refused".to_string(),
+            connected: false,
+        }
+    );
+    assert_eq!(
+        send(&url).to_string(),
+        format!("transport error: connection refused at {}", url)
+    );
+}
+
+#[test]
+fn tls_handshake_failure() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!(
+        "https://127.0.0.1:{}/",
+        listener.local_addr().unwrap().port()
+    );
+
+    // Replies in plain text, as a server not configured for TLS would.
+    thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut buf = [0; 1024];
+
+        let _ = stream.read(&mut buf);
+        let _ = stream.write_all(b"HTTP/1.1 400 Bad Request\r\n\r\n");
+    });
+
+    let error = send(&url);
+
+    assert_eq!(error.kind(), "tls", "{}", error);
+}
+
+#[test]
+fn missing_certificate() {
+    let path = env::temp_dir().join("etsi_014_test_suite_missing.pem");
+    let path = path.to_str().unwrap();
+    let error = common::load_identity(&sae(path)).err().unwrap();
+
+    assert!(
+        matches!(
+            &error,
+            Error::Certificate { path: val, reason }
+                if val == path && reason.starts_with("cannot be read")
+        ),
+        "{}",
+        error
+    );
+}
+
+#[test]
+fn invalid_certificate() {
+    let path = env::temp_dir().join("etsi_014_test_suite_invalid.crt");
+    let path = path.to_str().unwrap();
+
+    fs::write(path, "not a certificate").unwrap();
+
+    let error = common::load_identity(&sae(path)).err().unwrap();
+
+    assert_eq!(
+        error.to_string(),
+        format!(
+            "certificate error: '{}': neither PEM, PKCS#12 nor DER",
+            path
+        )
+    );
+}
@@ -1,30 +1,35 @@
 // SPDX-FileCopyrightText: © 2023 Merqury Cybersecurity Ltd <info@merqury.eu>
 // SPDX-License-Identifier: PolyForm-Noncommercial-1.0.0
 
-#[macro_use]
-extern crate lazy_static;
-
-mod common;
-mod models;
-
-use base64::Engine;
-use common::config::CONFIG;
-use models::{key, status::Status};
+use etsi_gs_qkd_014_testsuite::{
+    common::{
+        self,
+        capabilities::{self, CAPABILITIES},
+        client::{AsyncClient, Response},
+        concurrency::{self, MIN_RACE_THREADS},
+        config::{Side, CONFIG},
+        key_material::{self, Strictness},
+    },
+    models::{error_message::ErrorMessage, key, status::Status},
+};
 use pretty_assertions::assert_eq;
 use reqwest::{Method, StatusCode};
 use rstest::rstest;
 use serde_json::json;
+use std::{collections::HashSet, sync::Barrier, thread, time::Instant};
 
 #[rstest]
 #[case::using_get(Method::GET)]
 #[case::using_post(Method::POST)]
 fn successful_key_request_and_retrieval(#[case] request_method: Method) {
     let enc_keys_url =
-        format!("{}/{}/enc_keys", CONFIG.base_url, CONFIG.slave_sae_id);
+        format!("{}/{}/enc_keys", CONFIG.base_url, CONFIG.slave().id);
     let dec_keys_url =
-        format!("{}/{}/dec_keys", CONFIG.base_url, CONFIG.master_sae_id);
-    let master_client = common::build_client(&CONFIG.master_sae_crt);
-    let slave_client = common::build_client(&CONFIG.slave_sae_crt);
+        format!("{}/{}/dec_keys", CONFIG.slave_base_url, CONFIG.master().id);
+    let master_client =
+        common::build_client(CONFIG.master(), Side::Master).unwrap();
+    let slave_client =
+        common::build_client(CONFIG.slave(), Side::Slave).unwrap();
 
     // Request a key
     let enc_keys_response = match request_method {
@@ -82,7 +87,517 @@ fn successful_key_request_and_retrieval(#[case] request_method: Method) {
             }
         };
 
-    assert_eq!(retrieved_key_by_id, returned_keys);
+    assert_same_keys(&returned_keys, &retrieved_key_by_id);
+}
+
+#[rstest]
+#[case::using_get(Method::GET)]
+#[case::using_post(Method::POST)]
+fn key_material_encoding(#[case] request_method: Method) {
+    let enc_keys_url =
+        format!("{}/{}/enc_keys", CONFIG.base_url, CONFIG.slave().id);
+    let dec_keys_url =
+        format!("{}/{}/dec_keys", CONFIG.slave_base_url, CONFIG.master().id);
+    let master_client =
+        common::build_client(CONFIG.master(), Side::Master).unwrap();
+    let slave_client =
+        common::build_client(CONFIG.slave(), Side::Slave).unwrap();
+    let num_keys = *CAPABILITIES.key_counts().last().unwrap();
+
+    if let Some(reason) =
+        CAPABILITIES.unsupported_reason(num_keys, CAPABILITIES.status.key_size)
+    {
+        capabilities::skip(&reason);
+        return;
+    }
+
+    // Request keys
+    let enc_keys_response = match request_method {
+        Method::GET => master_client
+            .request(request_method.clone(), enc_keys_url)
+            .query(&[("number", num_keys)])
+            .send()
+            .unwrap(),
+        Method::POST => master_client
+            .request(request_method.clone(), enc_keys_url)
+            .json(&json!({ "number": num_keys }))
+            .send()
+            .unwrap(),
+        _ => {
+            panic!("Only 'GET' and 'POST' methods are supported")
+        }
+    };
+
+    assert!(enc_keys_response.status().is_success());
+
+    let returned_keys = match enc_keys_response.json::<key::KeyContainer>() {
+        Ok(parsed_body) => parsed_body,
+        Err(e) => {
+            panic!("Invalid response given. Error: {:?}", e);
+        }
+    };
+
+    // Request the keys retrieved based on their ids. A `GET` request names a
+    // single key, so each key is retrieved by a request of its own.
+    let dec_keys_requests: Vec<_> = match request_method {
+        Method::GET => returned_keys
+            .keys
+            .iter()
+            .map(|key| {
+                slave_client
+                    .request(request_method.clone(), &dec_keys_url)
+                    .query(&[("key_ID", key.key_id)])
+            })
+            .collect(),
+        Method::POST => vec![slave_client
+            .request(request_method, &dec_keys_url)
+            .json(&returned_keys)],
+        _ => {
+            panic!("Only 'GET' and 'POST' methods are supported")
+        }
+    };
+    let mut retrieved_keys = key::KeyContainer { keys: Vec::new() };
+
+    for dec_keys_request in dec_keys_requests {
+        let dec_keys_response = dec_keys_request.send().unwrap();
+
+        assert!(dec_keys_response.status().is_success());
+
+        match dec_keys_response.json::<key::KeyContainer>() {
+            Ok(parsed_body) => retrieved_keys.keys.extend(parsed_body.keys),
+            Err(e) => {
+                panic!("Invalid response given. Error: {:?}", e);
+            }
+        }
+    }
+
+    assert_eq!(
+        retrieved_keys.keys.len(),
+        returned_keys.keys.len(),
+        "The number of keys retrieved differs from the number requested"
+    );
+
+    // The key material itself is compared by the other tests, only its
+    // encoding is checked here. Non-canonical encodings are only warned about
+    // unless the strictness requires otherwise.
+    let mut issues = Vec::new();
+    let mut warnings = Vec::new();
+
+    for (returned, retrieved) in
+        returned_keys.keys.iter().zip(&retrieved_keys.keys)
+    {
+        for (side, key) in [("master", returned), ("slave", retrieved)] {
+            match key_material::decode(key.key.as_deref().unwrap_or_default()) {
+                Ok((_, encoding)) if !encoding.is_canonical() => {
+                    let finding = format!(
+                        "key '{}' returned to the {} SAE uses the {} \
+                         encoding instead of the standard, padded, one",
+                        key.key_id, side, encoding
+                    );
+
+                    match CONFIG.base64_strictness {
+                        Strictness::Warn => warnings.push(finding),
+                        Strictness::Fail => issues.push(finding),
+                    }
+                }
+                Ok(_) => {}
+                Err(e) => issues.push(format!(
+                    "key '{}' returned to the {} SAE is not base64: {}",
+                    key.key_id, side, e
+                )),
+            }
+        }
+
+        if returned.key != retrieved.key {
+            issues.push(format!(
+                "key '{}' is encoded differently for the master and slave SAEs",
+                returned.key_id
+            ));
+        }
+    }
+
+    for warning in &warnings {
+        eprintln!("Warning: {}", warning);
+    }
+
+    assert!(issues.is_empty(), "Encoding issues found: {:#?}", issues);
+}
+
+#[rstest]
+#[case::using_get(Method::GET)]
+#[case::using_post(Method::POST)]
+#[tokio::test(flavor = "multi_thread")]
+async fn concurrent_key_request_and_retrieval(#[case] request_method: Method) {
+    let master_client =
+        common::build_async_client(CONFIG.master(), Side::Master).unwrap();
+    let slave_client =
+        common::build_async_client(CONFIG.slave(), Side::Slave).unwrap();
+    // Request more keys than the concurrency limit to keep all the slots busy.
+    let num_requests = CONFIG.max_concurrency.get() * 2;
+
+    let results =
+        common::concurrency::run_bounded((0..num_requests).map(|_| {
+            async_key_request_and_retrieval(
+                &master_client,
+                &slave_client,
+                request_method.clone(),
+            )
+        }))
+        .await;
+
+    for (returned_keys, retrieved_key_by_id) in results {
+        assert_same_keys(&returned_keys, &retrieved_key_by_id);
+    }
+}
+
+/// Requests a single key as the master SAE and retrieves it by its ID as the
+/// slave SAE, returning both replies.
+async fn async_key_request_and_retrieval(
+    master_client: &AsyncClient,
+    slave_client: &AsyncClient,
+    request_method: Method,
+) -> (key::KeyContainer, key::KeyContainer) {
+    let enc_keys_url =
+        format!("{}/{}/enc_keys", CONFIG.base_url, CONFIG.slave().id);
+    let dec_keys_url =
+        format!("{}/{}/dec_keys", CONFIG.slave_base_url, CONFIG.master().id);
+
+    // Request a key
+    let enc_keys_request = match request_method {
+        Method::GET => master_client
+            .request(request_method.clone(), enc_keys_url)
+            .query(&[("number", 1)]),
+        Method::POST => master_client
+            .request(request_method.clone(), enc_keys_url)
+            .json(&json!({"number": 1})),
+        _ => {
+            panic!("Only 'GET' and 'POST' methods are supported")
+        }
+    };
+    let enc_keys_response = enc_keys_request.send().await.unwrap();
+
+    assert!(enc_keys_response.status().is_success());
+
+    let returned_keys = match enc_keys_response.json::<key::KeyContainer>() {
+        Ok(parsed_body) => parsed_body,
+        Err(e) => {
+            panic!("Invalid response given. Error: {:?}", e);
+        }
+    };
+
+    assert_eq!(returned_keys.keys.len(), 1);
+
+    // Request the key retrieved based on its id
+    let dec_keys_request = match request_method {
+        Method::GET => slave_client
+            .request(request_method, dec_keys_url)
+            .query(&[("key_ID", returned_keys.keys[0].key_id)]),
+        Method::POST => slave_client
+            .request(request_method, dec_keys_url)
+            .json(&returned_keys),
+        _ => {
+            panic!("Only 'GET' and 'POST' methods are supported")
+        }
+    };
+    let dec_keys_response = dec_keys_request.send().await.unwrap();
+
+    assert!(dec_keys_response.status().is_success());
+
+    let retrieved_key_by_id =
+        match dec_keys_response.json::<key::KeyContainer>() {
+            Ok(parsed_body) => parsed_body,
+            Err(e) => {
+                panic!("Invalid response given. Error: {:?}", e);
+            }
+        };
+
+    (returned_keys, retrieved_key_by_id)
+}
+
+/// Asserts that the keys retrieved by the slave SAE are the keys returned to
+/// the master SAE. The key material is decoded and compared byte by byte, so
+/// that equivalent encodings are not reported as mismatches; the encoding
+/// itself is checked by `key_material_encoding`.
+fn assert_same_keys(
+    returned_keys: &key::KeyContainer,
+    retrieved_keys: &key::KeyContainer,
+) {
+    assert_eq!(
+        retrieved_keys.keys.iter().map(|key| key.key_id).collect::<Vec<_>>(),
+        returned_keys.keys.iter().map(|key| key.key_id).collect::<Vec<_>>()
+    );
+
+    for (returned, retrieved) in
+        returned_keys.keys.iter().zip(&retrieved_keys.keys)
+    {
+        let [returned_bytes, retrieved_bytes] =
+            [("master", returned), ("slave", retrieved)].map(|(side, key)| {
+                match key_material::decode(
+                    key.key.as_deref().unwrap_or_default(),
+                ) {
+                    Ok((val, _)) => val,
+                    Err(e) => panic!(
+                        "Failed to decode the key '{}' returned to the {} \
+                         SAE. Error: {}",
+                        key.key_id, side, e
+                    ),
+                }
+            });
+
+        assert!(
+            returned_bytes == retrieved_bytes,
+            "The key material of key '{}' differs between the master and \
+             slave SAEs",
+            returned.key_id
+        );
+    }
+}
+
+#[rstest]
+#[case::using_get(Method::GET)]
+#[case::using_post(Method::POST)]
+fn concurrent_retrieval_of_same_key(#[case] request_method: Method) {
+    let enc_keys_url =
+        format!("{}/{}/enc_keys", CONFIG.base_url, CONFIG.slave().id);
+    let dec_keys_url =
+        format!("{}/{}/dec_keys", CONFIG.slave_base_url, CONFIG.master().id);
+    let master_client =
+        common::build_client(CONFIG.master(), Side::Master).unwrap();
+    let num_threads = concurrency::race_threads();
+
+    let enc_keys_response = master_client
+        .post(enc_keys_url)
+        .json(&json!({"number": 1}))
+        .send()
+        .unwrap();
+
+    assert!(enc_keys_response.status().is_success());
+
+    let returned_keys = match enc_keys_response.json::<key::KeyContainer>() {
+        Ok(parsed_body) => parsed_body,
+        Err(e) => {
+            panic!("Invalid response given. Error: {:?}", e);
+        }
+    };
+
+    assert_eq!(returned_keys.keys.len(), 1);
+
+    // All the threads are released at the same time to maximise the chance of
+    // the requests being handled simultaneously by the KME.
+    let barrier = Barrier::new(num_threads);
+    let exchanges: Vec<(Response, (Instant, Instant))> =
+        thread::scope(|scope| {
+            let handles: Vec<_> = (0..num_threads)
+                .map(|_| {
+                    // Named after the check, so that the requests are attributed
+                    // to it when recorded.
+                    thread::Builder::new()
+                        .name(common::check_name())
+                        .spawn_scoped(scope, || {
+                            let slave_client = common::build_client(
+                                CONFIG.slave(),
+                                Side::Slave,
+                            )
+                            .unwrap();
+                            let dec_keys_request = match request_method {
+                                Method::GET => slave_client
+                                    .request(
+                                        request_method.clone(),
+                                        &dec_keys_url,
+                                    )
+                                    .query(&[(
+                                        "key_ID",
+                                        returned_keys.keys[0].key_id,
+                                    )]),
+                                Method::POST => slave_client
+                                    .request(
+                                        request_method.clone(),
+                                        &dec_keys_url,
+                                    )
+                                    .json(&returned_keys),
+                                _ => {
+                                    panic!(
+                                "Only 'GET' and 'POST' methods are supported"
+                            )
+                                }
+                            };
+
+                            barrier.wait();
+                            let sent = Instant::now();
+                            let response = dec_keys_request.send().unwrap();
+
+                            (response, (sent, Instant::now()))
+                        })
+                        .unwrap()
+                })
+                .collect();
+
+            handles.into_iter().map(|handle| handle.join().unwrap()).collect()
+        });
+
+    let (responses, times): (Vec<Response>, Vec<(Instant, Instant)>) =
+        exchanges.into_iter().unzip();
+
+    assert_racing(&times);
+
+    let (successful, failed): (Vec<Response>, Vec<Response>) = responses
+        .into_iter()
+        .partition(|response| response.status().is_success());
+
+    assert_eq!(
+        successful.len(),
+        1,
+        "The same key was retrieved {} times by {} concurrent requests",
+        successful.len(),
+        num_threads
+    );
+
+    for response in failed {
+        let status = response.status();
+        let response_text = response.text().unwrap();
+
+        assert!(
+            serde_json::from_str::<ErrorMessage>(&response_text).is_ok(),
+            "Invalid error message format returned with status {}. \
+             Response: {}",
+            status,
+            response_text
+        );
+    }
+
+    let retrieved_key_by_id = match successful
+        .into_iter()
+        .next()
+        .unwrap()
+        .json::<key::KeyContainer>()
+    {
+        Ok(parsed_body) => parsed_body,
+        Err(e) => {
+            panic!("Invalid response given. Error: {:?}", e);
+        }
+    };
+
+    assert_same_keys(&returned_keys, &retrieved_key_by_id);
+}
+
+#[rstest]
+#[case::using_get(Method::GET)]
+#[case::using_post(Method::POST)]
+fn concurrent_key_requests_return_unique_key_ids(
+    #[case] request_method: Method,
+) {
+    let enc_keys_url =
+        format!("{}/{}/enc_keys", CONFIG.base_url, CONFIG.slave().id);
+    let num_threads = concurrency::race_threads();
+    let num_keys = 2;
+
+    if let Some(reason) =
+        CAPABILITIES.unsupported_reason(num_keys, CAPABILITIES.status.key_size)
+    {
+        capabilities::skip(&reason);
+        return;
+    }
+
+    // Each thread acts as a separate master, with its own client and TLS
+    // session, and all of them are released at the same time.
+    let barrier = Barrier::new(num_threads);
+    let exchanges: Vec<(key::KeyContainer, (Instant, Instant))> =
+        thread::scope(|scope| {
+            let handles: Vec<_> = (0..num_threads)
+                .map(|_| {
+                    // Named after the check, so that the requests are attributed
+                    // to it when recorded.
+                    thread::Builder::new()
+                        .name(common::check_name())
+                        .spawn_scoped(scope, || {
+                            let master_client = common::build_client(
+                                CONFIG.master(),
+                                Side::Master,
+                            )
+                            .unwrap();
+                            let enc_keys_request = match request_method {
+                                Method::GET => master_client
+                                    .request(
+                                        request_method.clone(),
+                                        &enc_keys_url,
+                                    )
+                                    .query(&[("number", num_keys)]),
+                                Method::POST => master_client
+                                    .request(
+                                        request_method.clone(),
+                                        &enc_keys_url,
+                                    )
+                                    .json(&json!({ "number": num_keys })),
+                                _ => {
+                                    panic!(
+                                "Only 'GET' and 'POST' methods are supported"
+                            )
+                                }
+                            };
+
+                            barrier.wait();
+                            let sent = Instant::now();
+                            let enc_keys_response =
+                                enc_keys_request.send().unwrap();
+                            let received = Instant::now();
+
+                            assert!(enc_keys_response.status().is_success());
+
+                            match enc_keys_response.json::<key::KeyContainer>()
+                            {
+                                Ok(parsed_body) => {
+                                    (parsed_body, (sent, received))
+                                }
+                                Err(e) => {
+                                    panic!(
+                                        "Invalid response given. Error: {:?}",
+                                        e
+                                    );
+                                }
+                            }
+                        })
+                        .unwrap()
+                })
+                .collect();
+
+            handles.into_iter().map(|handle| handle.join().unwrap()).collect()
+        });
+
+    let (returned_keys, times): (Vec<key::KeyContainer>, Vec<_>) =
+        exchanges.into_iter().unzip();
+
+    assert_racing(&times);
+
+    let mut key_ids = HashSet::new();
+
+    for key_container in &returned_keys {
+        assert_eq!(key_container.keys.len(), num_keys as usize);
+
+        for key in &key_container.keys {
+            assert!(
+                key_ids.insert(key.key_id),
+                "Key ID '{}' was handed out more than once",
+                key.key_id
+            );
+        }
+    }
+}
+
+/// Asserts that the requests, given the times at which each one was sent and
+/// its response received, were in flight together and could race. Replayed
+/// requests are answered at once, so they are never in flight together.
+fn assert_racing(exchanges: &[(Instant, Instant)]) {
+    if CONFIG.replay.is_some() {
+        return;
+    }
+
+    let max_in_flight = concurrency::max_in_flight(exchanges);
+
+    assert!(
+        max_in_flight >= MIN_RACE_THREADS,
+        "At most {} request was in flight at a time, so nothing raced",
+        max_in_flight
+    );
 }
 
 #[rstest]
@@ -90,11 +605,13 @@ fn successful_key_request_and_retrieval(#[case] request_method: Method) {
 #[case::using_post(Method::POST)]
 fn unauthorized_access(#[case] request_method: Method) {
     let enc_keys_url =
-        format!("{}/{}/enc_keys", CONFIG.base_url, CONFIG.slave_sae_id);
+        format!("{}/{}/enc_keys", CONFIG.base_url, CONFIG.slave().id);
     let dec_keys_url =
-        format!("{}/{}/dec_keys", CONFIG.base_url, CONFIG.master_sae_id);
-    let master_client = common::build_client(&CONFIG.master_sae_crt);
-    let unauthorized_client = common::build_client(&CONFIG.add_slave_sae_crt);
+        format!("{}/{}/dec_keys", CONFIG.slave_base_url, CONFIG.master().id);
+    let master_client =
+        common::build_client(CONFIG.master(), Side::Master).unwrap();
+    let unauthorized_client =
+        common::build_client(CONFIG.outsider(), Side::Slave).unwrap();
 
     // Request a key
     let enc_keys_response = match request_method {
@@ -116,7 +633,7 @@ fn unauthorized_access(#[case] request_method: Method) {
     assert!(enc_keys_response.status().is_success());
 
     let key = match enc_keys_response.json::<key::KeyContainer>() {
-        Ok(parsed_body) => parsed_body.keys.get(0).unwrap().clone(),
+        Ok(parsed_body) => parsed_body.keys.first().unwrap().clone(),
         Err(e) => {
             panic!("Invalid response given. Error: {:?}", e);
         }
@@ -147,23 +664,34 @@ fn unauthorized_access(#[case] request_method: Method) {
 #[case::using_post(Method::POST)]
 fn additional_slave_sae_ids(#[case] request_method: Method) {
     let enc_keys_url =
-        format!("{}/{}/enc_keys", CONFIG.base_url, CONFIG.slave_sae_id);
+        format!("{}/{}/enc_keys", CONFIG.base_url, CONFIG.slave().id);
     let dec_keys_url =
-        format!("{}/{}/dec_keys", CONFIG.base_url, CONFIG.master_sae_id);
-    let master_client = common::build_client(&CONFIG.master_sae_crt);
+        format!("{}/{}/dec_keys", CONFIG.slave_base_url, CONFIG.master().id);
+    let master_client =
+        common::build_client(CONFIG.master(), Side::Master).unwrap();
+
+    if let Some(reason) = CAPABILITIES.unsupported_additional_slaves_reason(1) {
+        capabilities::skip(&reason);
+        return;
+    }
+
+    let additional_slave = CONFIG.extra_slaves()[0];
     let additional_slave_client =
-        common::build_client(&CONFIG.add_slave_sae_crt);
+        common::build_client(additional_slave, Side::Slave).unwrap();
 
     let enc_keys_response = master_client
         .post(enc_keys_url)
-        .json(&json!({"number": 1, "additional_slave_SAE_IDs": [CONFIG.add_slave_sae_id]}))
+        .json(&json!({
+            "number": 1,
+            "additional_slave_SAE_IDs": [additional_slave.id],
+        }))
         .send()
         .unwrap();
 
     assert!(enc_keys_response.status().is_success());
 
     let key = match enc_keys_response.json::<key::KeyContainer>() {
-        Ok(parsed_body) => parsed_body.keys.get(0).unwrap().clone(),
+        Ok(parsed_body) => parsed_body.keys.first().unwrap().clone(),
         Err(e) => {
             panic!("Invalid response given. Error: {:?}", e);
         }
@@ -190,13 +718,141 @@ fn additional_slave_sae_ids(#[case] request_method: Method) {
 
     let retrieved_key_by_id =
         match dec_keys_response.json::<key::KeyContainer>() {
-            Ok(parsed_body) => parsed_body.keys.get(0).unwrap().clone(),
+            Ok(parsed_body) => parsed_body.keys.first().unwrap().clone(),
+            Err(e) => {
+                panic!("Invalid response given. Error: {:?}", e);
+            }
+        };
+
+    assert_same_keys(
+        &key::KeyContainer { keys: vec![key] },
+        &key::KeyContainer {
+            keys: vec![retrieved_key_by_id],
+        },
+    );
+}
+
+#[rstest]
+#[case::using_get(Method::GET)]
+#[case::using_post(Method::POST)]
+fn multiple_additional_slave_sae_ids(#[case] request_method: Method) {
+    let enc_keys_url =
+        format!("{}/{}/enc_keys", CONFIG.base_url, CONFIG.slave().id);
+    let dec_keys_url =
+        format!("{}/{}/dec_keys", CONFIG.slave_base_url, CONFIG.master().id);
+    let master_client =
+        common::build_client(CONFIG.master(), Side::Master).unwrap();
+    let extra_slaves = CONFIG.extra_slaves();
+
+    // Counts only grow, so once a count is unsupported all the following
+    // ones are too.
+    for count in 2..=CAPABILITIES.status.max_sae_id_count.max(2) {
+        if let Some(reason) =
+            CAPABILITIES.unsupported_additional_slaves_reason(count)
+        {
+            capabilities::skip(&reason);
+            break;
+        }
+
+        let (listed, remaining) = extra_slaves.split_at(count as usize);
+        let mut unlisted = remaining.to_vec();
+
+        if let Some(outsider) = CONFIG.try_outsider() {
+            if !listed.contains(&outsider) && !unlisted.contains(&outsider) {
+                unlisted.push(outsider);
+            }
+        }
+
+        let enc_keys_response = master_client
+            .post(&enc_keys_url)
+            .json(&json!({
+                "number": 1,
+                "additional_slave_SAE_IDs": listed
+                    .iter()
+                    .map(|sae| &sae.id)
+                    .collect::<Vec<_>>(),
+            }))
+            .send()
+            .unwrap();
+
+        assert!(
+            enc_keys_response.status().is_success(),
+            "Failed to request a key for {} additional slave SAEs. Returned \
+             status: {}",
+            count,
+            enc_keys_response.status()
+        );
+
+        let key = match enc_keys_response.json::<key::KeyContainer>() {
+            Ok(parsed_body) => parsed_body.keys.first().unwrap().clone(),
             Err(e) => {
                 panic!("Invalid response given. Error: {:?}", e);
             }
         };
 
-    assert_eq!(retrieved_key_by_id, key);
+        if unlisted.is_empty() {
+            capabilities::skip(&format!(
+                "no unlisted SAE left to check with {} additional slave SAEs",
+                count
+            ));
+        }
+
+        // Unlisted SAEs are checked first, so that the key is still available
+        // if the KME wrongly hands it out.
+        for sae in unlisted.iter().chain(listed) {
+            let sae = *sae;
+            let client = common::build_client(sae, Side::Slave).unwrap();
+            let dec_keys_response = match request_method {
+                Method::GET => client
+                    .request(request_method.clone(), &dec_keys_url)
+                    .query(&[("key_ID", key.key_id)])
+                    .send()
+                    .unwrap(),
+                Method::POST => client
+                    .request(request_method.clone(), &dec_keys_url)
+                    .json(&json!({ "key_IDs": [key] }))
+                    .send()
+                    .unwrap(),
+                _ => {
+                    panic!("Only 'GET' and 'POST' methods are supported")
+                }
+            };
+
+            if unlisted.contains(&sae) {
+                assert_eq!(
+                    dec_keys_response.status(),
+                    StatusCode::UNAUTHORIZED,
+                    "Unlisted SAE '{}' was not refused the key",
+                    sae.id
+                );
+
+                continue;
+            }
+
+            assert!(
+                dec_keys_response.status().is_success(),
+                "Listed SAE '{}' failed to retrieve the key. Returned status: \
+                 {}",
+                sae.id,
+                dec_keys_response.status()
+            );
+
+            let retrieved_key_by_id =
+                match dec_keys_response.json::<key::KeyContainer>() {
+                    Ok(parsed_body) => parsed_body,
+                    Err(e) => {
+                        panic!("Invalid response given. Error: {:?}", e);
+                    }
+                };
+
+            assert_same_keys(
+                &key::KeyContainer {
+                    keys: vec![key.clone()],
+                },
+                &retrieved_key_by_id,
+            );
+        }
+    }
 }
 
 #[rstest]
@@ -204,10 +860,10 @@ fn additional_slave_sae_ids(#[case] request_method: Method) {
 #[case::using_post(Method::POST)]
 fn default_values_match_status_reply(#[case] request_method: Method) {
     let status_url =
-        format!("{}/{}/status", CONFIG.base_url, CONFIG.slave_sae_id);
+        format!("{}/{}/status", CONFIG.base_url, CONFIG.slave().id);
     let enc_keys_url =
-        format!("{}/{}/enc_keys", CONFIG.base_url, CONFIG.slave_sae_id);
-    let client = common::build_client(&CONFIG.master_sae_crt);
+        format!("{}/{}/enc_keys", CONFIG.base_url, CONFIG.slave().id);
+    let client = common::build_client(CONFIG.master(), Side::Master).unwrap();
 
     // Request status
     let status_response = client.get(&status_url).send().unwrap();
@@ -246,17 +902,129 @@ fn default_values_match_status_reply(#[case] request_method: Method) {
     // The default number of keys is 1.
     assert_eq!(key_container.keys.len(), 1);
 
-    let decoded_key = match base64::engine::general_purpose::STANDARD
-        .decode(key_container.keys[0].key.as_ref().unwrap())
-    {
-        Ok(val) => val,
-        Err(e) => panic!("Failed to decode key value. Error: {:?}", e),
+    let decoded_key = match key_material::decode(
+        key_container.keys[0].key.as_deref().unwrap_or_default(),
+    ) {
+        Ok((val, _)) => val,
+        Err(e) => panic!("Failed to decode key value. Error: {}", e),
     };
 
     assert_eq!(
         // base64 returns a vector of bytes, key_size is in bits, hence the
         // conversion.
-        i32::try_from(decoded_key.len()).unwrap() * 8,
+        i64::try_from(decoded_key.len()).unwrap() * 8,
         status_body.key_size
     );
 }
+
+#[rstest]
+#[case::using_get(Method::GET)]
+#[case::using_post(Method::POST)]
+fn key_size_sweep(#[case] request_method: Method) {
+    let enc_keys_url =
+        format!("{}/{}/enc_keys", CONFIG.base_url, CONFIG.slave().id);
+    let dec_keys_url =
+        format!("{}/{}/dec_keys", CONFIG.slave_base_url, CONFIG.master().id);
+    let master_client =
+        common::build_client(CONFIG.master(), Side::Master).unwrap();
+    let slave_client =
+        common::build_client(CONFIG.slave(), Side::Slave).unwrap();
+
+    for key_size_bits in
+        CAPABILITIES.key_size_sweep(CONFIG.key_size_stride.get())
+    {
+        if let Some(reason) = CAPABILITIES.unsupported_reason(1, key_size_bits)
+        {
+            capabilities::skip(&reason);
+            continue;
+        }
+
+        // Request a key of the given size
+        let enc_keys_response = match request_method {
+            Method::GET => master_client
+                .request(request_method.clone(), &enc_keys_url)
+                .query(&[("number", 1), ("size", key_size_bits)])
+                .send()
+                .unwrap(),
+            Method::POST => master_client
+                .request(request_method.clone(), &enc_keys_url)
+                .json(&json!({"number": 1, "size": key_size_bits}))
+                .send()
+                .unwrap(),
+            _ => {
+                panic!("Only 'GET' and 'POST' methods are supported")
+            }
+        };
+
+        assert!(
+            enc_keys_response.status().is_success(),
+            "Failed to request a {}-bit key. Returned status: {}",
+            key_size_bits,
+            enc_keys_response.status()
+        );
+
+        let returned_keys = match enc_keys_response.json::<key::KeyContainer>()
+        {
+            Ok(parsed_body) => parsed_body,
+            Err(e) => {
+                panic!("Invalid response given. Error: {:?}", e);
+            }
+        };
+
+        assert_eq!(returned_keys.keys.len(), 1);
+
+        // Request the key retrieved based on its id
+        let dec_keys_response = match request_method {
+            Method::GET => slave_client
+                .request(request_method.clone(), &dec_keys_url)
+                .query(&[("key_ID", returned_keys.keys[0].key_id)])
+                .send()
+                .unwrap(),
+            Method::POST => slave_client
+                .request(request_method.clone(), &dec_keys_url)
+                .json(&returned_keys)
+                .send()
+                .unwrap(),
+            _ => {
+                panic!("Only 'GET' and 'POST' methods are supported")
+            }
+        };
+
+        assert!(
+            dec_keys_response.status().is_success(),
+            "Failed to retrieve a {}-bit key. Returned status: {}",
+            key_size_bits,
+            dec_keys_response.status()
+        );
+
+        let retrieved_keys = match dec_keys_response.json::<key::KeyContainer>()
+        {
+            Ok(parsed_body) => parsed_body,
+            Err(e) => {
+                panic!("Invalid response given. Error: {:?}", e);
+            }
+        };
+
+        assert_eq!(retrieved_keys.keys.len(), 1);
+
+        for (side, key_container) in
+            [("master", &returned_keys), ("slave", &retrieved_keys)]
+        {
+            let decoded_key = match key_material::decode(
+                key_container.keys[0].key.as_deref().unwrap_or_default(),
+            ) {
+                Ok((val, _)) => val,
+                Err(e) => panic!("Failed to decode key value. Error: {}", e),
+            };
+
+            // base64 returns a vector of bytes, the key size is in bits.
+            assert_eq!(
+                decoded_key.len() * 8,
+                key_size_bits as usize,
+                "Wrong length of the {}-bit key returned to the {} SAE",
+                key_size_bits,
+                side
+            );
+        }
+    }
+}
@@ -0,0 +1,114 @@
+// SPDX-FileCopyrightText: © 2023 Merqury Cybersecurity Ltd <info@merqury.eu>
+// SPDX-License-Identifier: PolyForm-Noncommercial-1.0.0
+
+//! Tests of the HAR entries recorded for every exchange, built from exchanges
+//! made up by the tests, so they run without a KME.
+
+use etsi_gs_qkd_014_testsuite::common::{
+    har::{self, Outcome, Request, Tls},
+    redaction::Redaction,
+};
+use pretty_assertions::assert_eq;
+use reqwest::{
+    header::{HeaderMap, HeaderValue, CONTENT_TYPE},
+    Method, StatusCode, Url, Version,
+};
+use rstest::rstest;
+use serde_json::{json, Value};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+static KEYS: &str = r#"{"keys":[{"key_ID":"bc490419-7d60-487f-adc1-4ddcc177c139","key":"wHHVxRwDJs3/bXd38GHP3oe4svTuRpZS0yCC7x4Ly+s="}]}"#;
+
+fn entry(started: SystemTime, redaction: Redaction) -> Value {
+    let url =
+        Url::parse("https://kme/api/v1/keys/sae_2/enc_keys?number=1").unwrap();
+    let mut headers = HeaderMap::new();
+
+    headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+
+    har::entry(
+        started,
+        Request {
+            method: &Method::GET,
+            url: &url,
+            headers: &HeaderMap::new(),
+            body: None,
+        },
+        Outcome::Response {
+            status: StatusCode::OK,
+            version: Version::HTTP_11,
+            headers: &headers,
+            body: KEYS.as_bytes(),
+            wait: Duration::from_millis(20),
+            receive: Duration::from_millis(5),
+        },
+        &Tls {
+            sae_id: "sae_1".to_string(),
+            client_certificate: "sae_1.pem".to_string(),
+            root_certificates: vec!["root.crt".to_string()],
+        },
+        redaction,
+    )
+}
+
+fn response_text(entry: &Value) -> Value {
+    serde_json::from_str(entry["response"]["content"]["text"].as_str().unwrap())
+        .unwrap()
+}
+
+#[test]
+fn entry_describes_exchange() {
+    let entry = entry(UNIX_EPOCH, Redaction::Hash);
+
+    assert_eq!(entry["request"]["method"], "GET");
+    assert_eq!(
+        entry["request"]["queryString"],
+        json!([{"name": "number", "value": "1"}])
+    );
+    assert_eq!(entry["request"].get("postData"), None);
+    assert_eq!(entry["response"]["status"], 200);
+    assert_eq!(entry["response"]["statusText"], "OK");
+    assert_eq!(entry["response"]["httpVersion"], "HTTP/1.1");
+    assert_eq!(entry["response"]["content"]["mimeType"], "application/json");
+    assert_eq!(entry["response"]["bodySize"], KEYS.len());
+    assert_eq!(entry["time"], 25.0);
+    assert_eq!(entry["timings"]["wait"], 20.0);
+    assert_eq!(entry["_tls"]["saeId"], "sae_1");
+    assert_eq!(entry["_tls"]["rootCertificates"], json!(["root.crt"]));
+}
+
+#[rstest]
+#[case::hashed(Redaction::Hash, "<redacted sha256:")]
+#[case::truncated(Redaction::Truncate, "<redacted wHHV... (44 chars)>")]
+#[case::included(
+    Redaction::Off,
+    "wHHVxRwDJs3/bXd38GHP3oe4svTuRpZS0yCC7x4Ly+s="
+)]
+fn key_material(#[case] redaction: Redaction, #[case] expected: &str) {
+    let text = response_text(&entry(UNIX_EPOCH, redaction));
+
+    assert!(
+        text["keys"][0]["key"].as_str().unwrap().starts_with(expected),
+        "Unexpected key: {}",
+        text["keys"][0]["key"]
+    );
+    assert_eq!(
+        text["keys"][0]["key_ID"],
+        "bc490419-7d60-487f-adc1-4ddcc177c139"
+    );
+}
+
+#[rstest]
+#[case::epoch(0, 0, "1970-01-01T00:00:00.000Z")]
+#[case::milliseconds(1_675_168_496, 789, "2023-01-31T12:34:56.789Z")]
+#[case::leap_day(1_709_164_800, 1, "2024-02-29T00:00:00.001Z")]
+fn started_date_time(
+    #[case] secs: u64,
+    #[case] millis: u64,
+    #[case] expected: &str,
+) {
+    let started =
+        UNIX_EPOCH + Duration::from_secs(secs) + Duration::from_millis(millis);
+
+    assert_eq!(entry(started, Redaction::Hash)["startedDateTime"], expected);
+}
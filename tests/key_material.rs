@@ -0,0 +1,89 @@
+// SPDX-FileCopyrightText: © 2023 Merqury Cybersecurity Ltd <info@merqury.eu>
+// SPDX-License-Identifier: PolyForm-Noncommercial-1.0.0
+
+//! Tests of the decoding and redaction of key material, covering the base64
+//! variants a KME may return. They work on literal keys and can be run
+//! offline.
+
+use etsi_gs_qkd_014_testsuite::{
+    common::{
+        key_material::{self, Alphabet, Encoding},
+        redaction::Redaction,
+    },
+    models::key::KeyContainerElement,
+};
+use pretty_assertions::assert_eq;
+use rstest::rstest;
+use uuid::Uuid;
+
+#[rstest]
+#[case::standard_padded("+/8=", Alphabet::Standard, true, false, false)]
+#[case::standard_unpadded("+/8", Alphabet::Standard, false, false, false)]
+#[case::url_safe_padded("-_8=", Alphabet::UrlSafe, true, false, false)]
+#[case::url_safe_unpadded("-_8", Alphabet::UrlSafe, false, false, false)]
+#[case::whitespace(" +/8= ", Alphabet::Standard, true, true, false)]
+#[case::line_breaks("+/\r\n8=\n", Alphabet::Standard, true, false, true)]
+#[case::form_feed("+/\x0c8=", Alphabet::Standard, true, true, false)]
+fn equivalent_encodings_decode_to_same_bytes(
+    #[case] encoded: &str,
+    #[case] alphabet: Alphabet,
+    #[case] padded: bool,
+    #[case] whitespace: bool,
+    #[case] line_breaks: bool,
+) {
+    assert_eq!(
+        key_material::decode(encoded)
+            .map(|(bytes, encoding)| (bytes.to_vec(), encoding)),
+        Ok((
+            vec![0xfb, 0xff],
+            Encoding {
+                alphabet,
+                padded,
+                whitespace,
+                line_breaks
+            }
+        ))
+    );
+}
+
+#[rstest]
+#[case::standard_padded("+/8=", true)]
+#[case::no_padding_needed("AAAA", true)]
+#[case::standard_unpadded("+/8", false)]
+#[case::url_safe("-_8=", false)]
+#[case::whitespace("+/8= ", false)]
+#[case::line_breaks("AAAA\nAAAA", false)]
+#[case::form_feed("AAAA\x0cAAAA", false)]
+fn canonical_encoding(#[case] encoded: &str, #[case] canonical: bool) {
+    let (_, encoding) = key_material::decode(encoded).unwrap();
+
+    assert_eq!(encoding.is_canonical(), canonical);
+}
+
+#[rstest]
+#[case::mixed_alphabets("+_8=")]
+#[case::invalid_character("*AAA")]
+#[case::invalid_length("A")]
+fn invalid_key_material(#[case] encoded: &str) {
+    assert!(key_material::decode(encoded).is_err());
+}
+
+#[rstest]
+#[case::hash(Redaction::Hash, "<redacted sha256:e4c630c5135584b7>")]
+#[case::truncate(Redaction::Truncate, "<redacted +/8=... (4 chars)>")]
+#[case::off(Redaction::Off, "+/8=")]
+fn redaction_policy(#[case] redaction: Redaction, #[case] expected: &str) {
+    assert_eq!(redaction.apply("+/8="), expected);
+}
+
+#[test]
+fn key_container_debug_is_redacted() {
+    let element = KeyContainerElement {
+        key_id: Uuid::nil(),
+        key: Some("wHHVxRwDJs3/bXd38GHP3oe4svTuRpZS0yCC7x4Ly+s=".to_string()),
+    };
+    let printed = format!("{:?}", element);
+
+    assert!(!printed.contains("wHHVxRwDJs3"), "Leaked key: {}", printed);
+    assert!(printed.contains("<redacted sha256:"), "{}", printed);
+}
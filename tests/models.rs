@@ -0,0 +1,243 @@
+// SPDX-FileCopyrightText: © 2023 Merqury Cybersecurity Ltd <info@merqury.eu>
+// SPDX-License-Identifier: PolyForm-Noncommercial-1.0.0
+
+//! Property-based tests checking that the data models serialize as the
+//! standard describes and round-trip without loss. Nothing is sent to a KME.
+
+mod common;
+
+use etsi_gs_qkd_014_testsuite::models::{
+    error_message::ErrorMessage,
+    key::{KeyContainer, KeyContainerElement},
+    key_request::KeyRequest,
+    status::Status,
+};
+use proptest::{collection::vec, option, prelude::*};
+use serde_json::{json, Map, Value};
+use uuid::Uuid;
+
+fn sae_id() -> impl Strategy<Value = String> {
+    "\\PC{0,32}"
+}
+
+fn base64_key() -> impl Strategy<Value = String> {
+    "([A-Za-z0-9+/]{4}){0,64}([A-Za-z0-9+/]{2}==|[A-Za-z0-9+/]{3}=)?"
+}
+
+fn uuid() -> impl Strategy<Value = Uuid> {
+    any::<u128>().prop_map(Uuid::from_u128)
+}
+
+/// Extension and detail objects, whose contents are not defined by the
+/// standard.
+fn json_object() -> impl Strategy<Value = Map<String, Value>> {
+    let leaf = prop_oneof![
+        Just(Value::Null),
+        any::<bool>().prop_map(Value::from),
+        any::<i64>().prop_map(Value::from),
+        "\\PC{0,16}".prop_map(Value::from),
+    ];
+    let value = leaf.prop_recursive(3, 16, 4, |inner| {
+        prop_oneof![
+            vec(inner.clone(), 0..4).prop_map(Value::from),
+            proptest::collection::btree_map("\\PC{0,8}", inner, 0..4)
+                .prop_map(|map| Value::Object(map.into_iter().collect())),
+        ]
+    });
+
+    proptest::collection::btree_map("\\PC{0,8}", value, 0..4)
+        .prop_map(|map| map.into_iter().collect())
+}
+
+prop_compose! {
+    fn status()(
+        source_kme_id in sae_id(),
+        target_kme_id in sae_id(),
+        master_sae_id in sae_id(),
+        slave_sae_id in sae_id(),
+        numbers in any::<[i64; 7]>(),
+    ) -> Status {
+        Status {
+            source_kme_id,
+            target_kme_id,
+            master_sae_id,
+            slave_sae_id,
+            key_size: numbers[0],
+            stored_key_count: numbers[1],
+            max_key_count: numbers[2],
+            max_key_per_request: numbers[3],
+            max_key_size: numbers[4],
+            min_key_size: numbers[5],
+            max_sae_id_count: numbers[6],
+        }
+    }
+}
+
+prop_compose! {
+    fn key_request()(
+        number in option::of(any::<i64>()),
+        size in option::of(any::<i64>()),
+        additional_slave_sae_ids in option::of(vec(sae_id(), 0..4)),
+        extension_mandatory in option::of(vec(json_object(), 0..3)),
+        extension_optional in option::of(vec(json_object(), 0..3)),
+    ) -> KeyRequest {
+        KeyRequest {
+            number,
+            size,
+            additional_slave_sae_ids,
+            extension_mandatory,
+            extension_optional,
+        }
+    }
+}
+
+prop_compose! {
+    fn error_message()(
+        message in "\\PC{0,64}",
+        details in option::of(vec(json_object(), 0..3)),
+    ) -> ErrorMessage {
+        ErrorMessage { message, details }
+    }
+}
+
+proptest! {
+    #[test]
+    fn key_container_deserializes_from_spec_json(
+        keys in vec((uuid(), base64_key()), 0..8),
+    ) {
+        let body = json!({
+            "keys": keys
+                .iter()
+                .map(|(key_id, key)| json!({"key_ID": key_id, "key": key}))
+                .collect::<Vec<Value>>(),
+        });
+
+        let parsed = serde_json::from_value::<KeyContainer>(body).unwrap();
+
+        prop_assert_eq!(parsed.keys.len(), keys.len());
+
+        for (element, (key_id, key)) in parsed.keys.iter().zip(&keys) {
+            prop_assert_eq!(element.key_id, *key_id);
+            prop_assert_eq!(element.key.as_ref(), Some(key));
+        }
+    }
+
+    #[test]
+    fn key_container_serializes_to_key_ids_schema(
+        keys in vec((uuid(), option::of(base64_key())), 0..8),
+    ) {
+        let container = KeyContainer {
+            keys: keys
+                .iter()
+                .map(|(key_id, key)| KeyContainerElement {
+                    key_id: *key_id,
+                    key: key.clone(),
+                })
+                .collect(),
+        };
+
+        // Key material must never be sent back to the KME.
+        let expected = json!({
+            "key_IDs": keys
+                .iter()
+                .map(|(key_id, _)| json!({"key_ID": key_id.to_string()}))
+                .collect::<Vec<Value>>(),
+        });
+
+        prop_assert_eq!(serde_json::to_value(&container).unwrap(), expected);
+    }
+
+    #[test]
+    fn status_round_trip(status in status()) {
+        let serialized = serde_json::to_value(&status).unwrap();
+        let mut field_names: Vec<&String> =
+            serialized.as_object().unwrap().keys().collect();
+
+        field_names.sort();
+
+        prop_assert_eq!(
+            field_names,
+            [
+                "key_size",
+                "master_SAE_ID",
+                "max_SAE_ID_count",
+                "max_key_count",
+                "max_key_per_request",
+                "max_key_size",
+                "min_key_size",
+                "slave_SAE_ID",
+                "source_KME_ID",
+                "stored_key_count",
+                "target_KME_ID",
+            ]
+        );
+        prop_assert_eq!(serde_json::from_value::<Status>(serialized).unwrap(), status);
+    }
+
+    #[test]
+    fn status_accepts_wide_and_float_integers(
+        wide in any::<i64>(),
+        float in -(1_i64 << 53)..(1_i64 << 53),
+    ) {
+        let mut body = serde_json::to_value(common::status()).unwrap();
+
+        body["max_key_count"] = json!(wide);
+        body["key_size"] = json!(float as f64);
+
+        let parsed = serde_json::from_value::<Status>(body).unwrap();
+
+        prop_assert_eq!(parsed.max_key_count, wide);
+        prop_assert_eq!(parsed.key_size, float);
+    }
+
+    #[test]
+    fn key_request_round_trip(key_request in key_request()) {
+        let serialized = serde_json::to_value(&key_request).unwrap();
+        let fields = serialized.as_object().unwrap();
+
+        // Fields that are not set are omitted, rather than sent as `null`.
+        prop_assert_eq!(fields.contains_key("number"), key_request.number.is_some());
+        prop_assert_eq!(fields.contains_key("size"), key_request.size.is_some());
+        prop_assert_eq!(
+            fields.contains_key("additional_slave_SAE_IDs"),
+            key_request.additional_slave_sae_ids.is_some()
+        );
+        prop_assert!(fields.values().all(|val| !val.is_null()));
+        prop_assert_eq!(
+            serde_json::from_value::<KeyRequest>(serialized).unwrap(),
+            key_request
+        );
+    }
+
+    #[test]
+    fn error_message_round_trip(error_message in error_message()) {
+        let serialized = serde_json::to_string(&error_message).unwrap();
+
+        prop_assert_eq!(
+            serde_json::from_str::<ErrorMessage>(&serialized).unwrap(),
+            error_message
+        );
+    }
+}
+
+#[test]
+fn status_type_mismatch_names_field() {
+    for (val, expected) in [
+        (
+            json!(256.5),
+            "max_SAE_ID_count: invalid value: floating point",
+        ),
+        (json!(u64::MAX), "max_SAE_ID_count: invalid value: integer"),
+        (json!("256"), "max_SAE_ID_count: invalid type: string"),
+    ] {
+        let mut body = serde_json::to_value(common::status()).unwrap();
+
+        body["max_SAE_ID_count"] = val;
+
+        let error = serde_path_to_error::deserialize::<_, Status>(body)
+            .unwrap_err()
+            .to_string();
+
+        assert!(error.starts_with(expected), "Unexpected error: {}", error);
+    }
+}
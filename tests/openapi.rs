@@ -0,0 +1,193 @@
+// SPDX-FileCopyrightText: © 2023 Merqury Cybersecurity Ltd <info@merqury.eu>
+// SPDX-License-Identifier: PolyForm-Noncommercial-1.0.0
+
+//! Tests of the validation of responses against the bundled OpenAPI document,
+//! with conforming and non-conforming responses written out by hand instead
+//! of received from a KME.
+
+mod common;
+
+use etsi_gs_qkd_014_testsuite::common::{
+    openapi::validate_response, spec::SpecVersion,
+};
+use pretty_assertions::assert_eq;
+use reqwest::{Method, StatusCode, Url};
+use rstest::rstest;
+use serde_json::json;
+
+static BASE_URL: &str = "https://kme.example/api/v1/keys";
+
+fn validate(
+    method: Method,
+    path: &str,
+    status: StatusCode,
+    content_type: Option<&str>,
+    body: &str,
+) -> Vec<String> {
+    let url = Url::parse(&format!("{}/{}", BASE_URL, path)).unwrap();
+
+    validate_response(
+        SpecVersion::V1_1_1,
+        &method,
+        &url,
+        status,
+        content_type,
+        body.as_bytes(),
+    )
+}
+
+fn key_container() -> String {
+    json!({
+        "keys": [{
+            "key_ID": "bc490419-7d60-487f-adc1-4ddcc177c139",
+            "key": "wHHVxRwDJs3/bXd38GHP3oe4svTuRpZS0yCC7x4Ly+s="
+        }]
+    })
+    .to_string()
+}
+
+#[rstest]
+#[case::status(
+    Method::GET,
+    "sae_2/status",
+    StatusCode::OK,
+    serde_json::to_string(&common::status()).unwrap()
+)]
+#[case::get_enc_keys(
+    Method::GET,
+    "sae_2/enc_keys",
+    StatusCode::OK,
+    key_container()
+)]
+#[case::post_dec_keys(
+    Method::POST,
+    "sae_1/dec_keys",
+    StatusCode::OK,
+    key_container()
+)]
+#[case::error(
+    Method::GET,
+    "sae_2/enc_keys",
+    StatusCode::BAD_REQUEST,
+    json!({"message": "size shall be a multiple of 8"}).to_string()
+)]
+#[case::undefined_method(
+    Method::PUT,
+    "sae_2/status",
+    StatusCode::METHOD_NOT_ALLOWED,
+    "".to_string()
+)]
+fn conforming_response(
+    #[case] method: Method,
+    #[case] path: &str,
+    #[case] status: StatusCode,
+    #[case] body: String,
+) {
+    assert_eq!(
+        validate(method, path, status, Some("application/json"), &body),
+        Vec::<String>::new()
+    );
+}
+
+#[rstest]
+#[case::undefined_status(
+    StatusCode::UNSUPPORTED_MEDIA_TYPE,
+    Some("application/json"),
+    r#"{"message": "unsupported"}"#,
+    "status 415 is not defined"
+)]
+#[case::wrong_content_type(
+    StatusCode::OK,
+    Some("text/plain"),
+    "ok",
+    "content type 'text/plain' is not defined"
+)]
+#[case::missing_content_type(
+    StatusCode::OK,
+    None,
+    "ok",
+    "content type <none> is not defined"
+)]
+#[case::invalid_json(
+    StatusCode::OK,
+    Some("application/json"),
+    "{",
+    "body is not valid JSON"
+)]
+#[case::missing_keys(
+    StatusCode::OK,
+    Some("application/json"),
+    "{}",
+    "/: missing required field 'keys'"
+)]
+#[case::invalid_key_id(
+    StatusCode::OK,
+    Some("application/json"),
+    r#"{"keys": [{"key_ID": "1", "key": "AA=="}]}"#,
+    "/keys/0/key_ID: '1' is not a UUID"
+)]
+#[case::invalid_key(
+    StatusCode::OK,
+    Some("application/json"),
+    r#"{"keys": [{"key_ID": "bc490419-7d60-487f-adc1-4ddcc177c139", "key": "*"}]}"#,
+    // The key material is redacted, following the default policy.
+    "/keys/0/key: '<redacted sha256:"
+)]
+#[case::wrong_type(
+    StatusCode::BAD_REQUEST,
+    Some("application/json"),
+    r#"{"message": 1}"#,
+    "/message: expected string, found 1"
+)]
+#[case::key_container_of_wrong_type(
+    StatusCode::OK,
+    Some("application/json"),
+    r#"{"keys": {"key_ID": "bc490419-7d60-487f-adc1-4ddcc177c139", "key": "AA=="}}"#,
+    // Containers of the wrong type are not printed, as they may hold keys.
+    "/keys: expected array, found an object"
+)]
+#[case::key_of_wrong_type(
+    StatusCode::OK,
+    Some("application/json"),
+    r#"{"keys": ["AA=="]}"#,
+    "/keys/0: expected object, found '<redacted sha256:"
+)]
+fn non_conforming_response(
+    #[case] status: StatusCode,
+    #[case] content_type: Option<&str>,
+    #[case] body: &str,
+    #[case] expected_violation: &str,
+) {
+    let violations =
+        validate(Method::GET, "sae_2/enc_keys", status, content_type, body);
+
+    assert_eq!(violations.len(), 1, "Violations: {:?}", violations);
+    assert!(
+        violations[0].starts_with(expected_violation),
+        "Unexpected violation: {}",
+        violations[0]
+    );
+}
+
+#[rstest]
+fn status_out_of_int32_range() {
+    let mut body = serde_json::to_value(common::status()).unwrap();
+
+    body["stored_key_count"] = json!(4294967296u64);
+    body.as_object_mut().unwrap().remove("max_SAE_ID_count");
+
+    assert_eq!(
+        validate(
+            Method::GET,
+            "sae_2/status",
+            StatusCode::OK,
+            Some("application/json; charset=utf-8"),
+            &body.to_string()
+        ),
+        vec![
+            "/: missing required field 'max_SAE_ID_count'".to_string(),
+            "/stored_key_count: 4294967296 is out of the int32 range"
+                .to_string(),
+        ]
+    );
+}
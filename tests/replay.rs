@@ -0,0 +1,242 @@
+// SPDX-FileCopyrightText: © 2023 Merqury Cybersecurity Ltd <info@merqury.eu>
+// SPDX-License-Identifier: PolyForm-Noncommercial-1.0.0
+
+//! Tests of the replay of recorded exchanges, matching requests against
+//! in-memory HAR entries. Like replay itself, they need no KME.
+
+use etsi_gs_qkd_014_testsuite::common::{error::Error, replay::Replay};
+use pretty_assertions::assert_eq;
+use reqwest::{header::CONTENT_TYPE, Method, StatusCode, Url};
+use serde_json::{json, Value};
+
+fn entry(
+    check: &str,
+    method: &str,
+    url: &str,
+    status: u16,
+    text: &str,
+) -> Value {
+    json!({
+        "_check": check,
+        "request": {"method": method, "url": url},
+        "response": {
+            "status": status,
+            "headers": [{"name": "content-type", "value": "application/json"}],
+            "content": {"text": text},
+        },
+    })
+}
+
+fn replay(entries: Vec<Value>) -> Replay {
+    Replay::new(json!({"log": {"entries": entries}}))
+}
+
+fn url(val: &str) -> Url {
+    Url::parse(val).unwrap()
+}
+
+#[test]
+fn replays_in_recorded_order() {
+    let check = "replays_in_recorded_order";
+    let replay = replay(vec![
+        entry(
+            check,
+            "GET",
+            "https://kme/keys/sae_2/enc_keys",
+            200,
+            "first",
+        ),
+        entry(
+            check,
+            "GET",
+            "https://kme/keys/sae_2/enc_keys",
+            400,
+            "second",
+        ),
+    ]);
+    let url = url("https://kme/keys/sae_2/enc_keys");
+
+    let first = replay.respond(&Method::GET, &url, None).unwrap();
+    let second = replay.respond(&Method::GET, &url, None).unwrap();
+
+    assert_eq!(first.status, StatusCode::OK);
+    assert_eq!(first.body, b"first");
+    assert_eq!(first.headers[CONTENT_TYPE], "application/json");
+    assert_eq!(second.status, StatusCode::BAD_REQUEST);
+    assert_eq!(second.body, b"second");
+    assert!(replay.respond(&Method::GET, &url, None).is_err());
+}
+
+#[test]
+fn matches_method_and_path_only() {
+    let check = "matches_method_and_path_only";
+    let replay = replay(vec![
+        entry(
+            check,
+            "POST",
+            "https://kme/keys/sae_1/dec_keys",
+            200,
+            "post",
+        ),
+        entry(
+            check,
+            "GET",
+            "https://kme/keys/sae_1/dec_keys?key_ID=a",
+            200,
+            "get",
+        ),
+    ]);
+
+    // The query string is not compared, as it may have been generated at
+    // random, and exchanges recorded earlier for another method are skipped.
+    let get = replay
+        .respond(
+            &Method::GET,
+            &url("https://other/keys/sae_1/dec_keys?key_ID=b"),
+            None,
+        )
+        .unwrap();
+    let post = replay
+        .respond(&Method::POST, &url("https://kme/keys/sae_1/dec_keys"), None)
+        .unwrap();
+
+    assert_eq!(get.body, b"get");
+    assert_eq!(post.body, b"post");
+}
+
+#[test]
+fn prefers_exchange_with_same_key_ids() {
+    let check = "prefers_exchange_with_same_key_ids";
+    let mut post = entry(
+        check,
+        "POST",
+        "https://kme/keys/sae_1/dec_keys",
+        200,
+        "post b",
+    );
+
+    post["request"]["postData"] =
+        json!({"text": r#"{"key_IDs":[{"key_ID":"b"}]}"#});
+
+    let replay = replay(vec![
+        entry(
+            check,
+            "GET",
+            "https://kme/keys/sae_1/dec_keys?key_ID=a",
+            200,
+            "get a",
+        ),
+        entry(
+            check,
+            "GET",
+            "https://kme/keys/sae_1/dec_keys?key_ID=b",
+            200,
+            "get b",
+        ),
+        entry(
+            check,
+            "POST",
+            "https://kme/keys/sae_1/dec_keys",
+            200,
+            "post a",
+        ),
+        post,
+    ]);
+
+    // Keys retrieved concurrently may have been recorded in another order.
+    let get = replay
+        .respond(
+            &Method::GET,
+            &url("https://kme/keys/sae_1/dec_keys?key_ID=b"),
+            None,
+        )
+        .unwrap();
+    let post = replay
+        .respond(
+            &Method::POST,
+            &url("https://kme/keys/sae_1/dec_keys"),
+            Some(br#"{"key_IDs":[{"key_ID":"b"}]}"#),
+        )
+        .unwrap();
+
+    assert_eq!(get.body, b"get b");
+    assert_eq!(post.body, b"post b");
+}
+
+#[test]
+fn replays_exchanges_of_current_check_only() {
+    let replay = replay(vec![entry(
+        "another_check",
+        "GET",
+        "https://kme/keys/sae_2/status",
+        200,
+        "{}",
+    )]);
+
+    let error = replay
+        .respond(&Method::GET, &url("https://kme/keys/sae_2/status"), None)
+        .err()
+        .unwrap();
+
+    assert_eq!(error.kind(), "replay");
+    assert!(
+        error.reason().contains("no recorded exchange left"),
+        "{}",
+        error
+    );
+}
+
+#[test]
+fn failed_exchange_is_replayed() {
+    let mut failed = entry(
+        "failed_exchange_is_replayed",
+        "POST",
+        "https://kme/keys/sae_2/enc_keys",
+        0,
+        "",
+    );
+
+    failed["_error"] = json!({
+        "message": "transport error: connection reset at https://kme/keys/sae_2/enc_keys",
+        "kind": "transport",
+        "reason": "connection reset",
+        "connected": true,
+    });
+
+    let error = replay(vec![failed])
+        .respond(&Method::POST, &url("https://kme/keys/sae_2/enc_keys"), None)
+        .err()
+        .unwrap();
+
+    assert_eq!(
+        error,
+        Error::Transport {
+            url: "https://kme/keys/sae_2/enc_keys".to_string(),
+            reason: "connection reset".to_string(),
+            connected: true,
+        }
+    );
+}
+
+#[test]
+fn failed_tls_handshake_is_replayed() {
+    let mut failed = entry(
+        "failed_tls_handshake_is_replayed",
+        "GET",
+        "https://kme/keys/sae_2/status",
+        0,
+        "",
+    );
+
+    failed["_error"] = json!({"kind": "tls", "reason": "unknown issuer"});
+
+    let error = replay(vec![failed])
+        .respond(&Method::GET, &url("https://kme/keys/sae_2/status"), None)
+        .err()
+        .unwrap();
+
+    assert_eq!(
+        error.to_string(),
+        "TLS error: unknown issuer at https://kme/keys/sae_2/status"
+    );
+}
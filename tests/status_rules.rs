@@ -0,0 +1,118 @@
+// SPDX-FileCopyrightText: © 2023 Merqury Cybersecurity Ltd <info@merqury.eu>
+// SPDX-License-Identifier: PolyForm-Noncommercial-1.0.0
+
+//! Tests of the semantic rules of `Status` replies, each broken in turn by
+//! overriding a field of a valid reply. The replies are fixtures, so no KME
+//! is needed.
+
+mod common;
+
+use etsi_gs_qkd_014_testsuite::common::status_rules::Rule;
+use pretty_assertions::assert_eq;
+use rstest::rstest;
+use serde_json::{json, Value};
+
+fn status(overrides: Value) -> Value {
+    let mut status = serde_json::to_value(common::status()).unwrap();
+
+    for (field, val) in overrides.as_object().unwrap() {
+        status[field] = val.clone();
+    }
+
+    status
+}
+
+#[test]
+fn valid_status_satisfies_every_rule() {
+    let status = status(json!({}));
+
+    for rule in Rule::ALL {
+        assert_eq!(rule.check(&status), Ok(()), "{}", rule);
+    }
+}
+
+#[rstest]
+#[case::empty_source_kme_id(
+    Rule::KmeIdsNotEmpty,
+    json!({"source_KME_ID": ""}),
+    "source_KME_ID"
+)]
+#[case::empty_target_kme_id(
+    Rule::KmeIdsNotEmpty,
+    json!({"target_KME_ID": ""}),
+    "target_KME_ID"
+)]
+#[case::overflowing_integer(
+    Rule::IntegersFitInI32,
+    json!({"max_key_count": 2_147_483_648_i64}),
+    "max_key_count is 2147483648"
+)]
+#[case::negative_overflowing_integer(
+    Rule::IntegersFitInI32,
+    json!({"stored_key_count": -2_147_483_649_i64}),
+    "stored_key_count"
+)]
+#[case::fractional_integer(
+    Rule::IntegersFitInI32,
+    json!({"key_size": 256.5}),
+    "key_size is 256.5"
+)]
+#[case::string_integer(
+    Rule::IntegersFitInI32,
+    json!({"key_size": "256"}),
+    "key_size"
+)]
+#[case::key_size_below_min(
+    Rule::KeySizeWithinRange,
+    json!({"key_size": 56}),
+    "key_size 56"
+)]
+#[case::key_size_above_max(
+    Rule::KeySizeWithinRange,
+    json!({"key_size": 2048}),
+    "key_size 2048"
+)]
+#[case::unaligned_key_size(
+    Rule::KeySizesMultipleOf8,
+    json!({"key_size": 100}),
+    "key_size is 100"
+)]
+#[case::unaligned_bounds(
+    Rule::KeySizesMultipleOf8,
+    json!({"min_key_size": 60, "max_key_size": 1020}),
+    "min_key_size is 60, max_key_size is 1020"
+)]
+#[case::too_many_stored_keys(
+    Rule::StoredKeyCountWithinMax,
+    json!({"stored_key_count": 1001}),
+    "stored_key_count 1001 exceeds max_key_count 1000"
+)]
+#[case::no_key_per_request(
+    Rule::MaxKeyPerRequestPositive,
+    json!({"max_key_per_request": 0}),
+    "max_key_per_request is 0"
+)]
+#[case::negative_sae_id_count(
+    Rule::MaxSaeIdCountNotNegative,
+    json!({"max_SAE_ID_count": -1}),
+    "max_SAE_ID_count is -1"
+)]
+fn violated_rule(
+    #[case] rule: Rule,
+    #[case] overrides: Value,
+    #[case] expected: &str,
+) {
+    let status = status(overrides);
+    let violation = rule.check(&status).unwrap_err();
+
+    assert!(
+        violation.contains(expected),
+        "Unexpected violation: {}",
+        violation
+    );
+
+    // Every other rule is unaffected, so that each problem is reported once.
+    for other in Rule::ALL.into_iter().filter(|other| *other != rule) {
+        assert_eq!(other.check(&status), Ok(()), "{}", other);
+    }
+}
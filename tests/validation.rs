@@ -1,17 +1,20 @@
 // SPDX-FileCopyrightText: © 2023 Merqury Cybersecurity Ltd <info@merqury.eu>
 // SPDX-License-Identifier: PolyForm-Noncommercial-1.0.0
 
-#[macro_use]
-extern crate lazy_static;
-
-mod common;
-mod models;
-
-use base64::Engine;
-use common::config::CONFIG;
-use models::{error_message::ErrorMessage, key, status::Status};
+use etsi_gs_qkd_014_testsuite::{
+    common::{
+        self,
+        capabilities::{self, CAPABILITIES},
+        client::{Client, Response},
+        config::{Side, CONFIG},
+        error::Error,
+        key_material,
+        status_rules::Rule,
+    },
+    models::{error_message::ErrorMessage, key, status::Status},
+};
 use pretty_assertions::assert_eq;
-use reqwest::{blocking::Response, Method, StatusCode};
+use reqwest::{header::CONTENT_TYPE, Method, StatusCode};
 use rstest::rstest;
 use serde_json::json;
 use uuid::Uuid;
@@ -21,8 +24,8 @@ use uuid::Uuid;
 #[case::negative_key_size("-8")]
 #[case::alphanumeric_key_size("abc01")]
 fn validate_key_size(#[case] key_size: &str) {
-    let client = common::build_client(&CONFIG.master_sae_crt);
-    let url = format!("{}/{}/enc_keys", CONFIG.base_url, CONFIG.slave_sae_id);
+    let client = common::build_client(CONFIG.master(), Side::Master).unwrap();
+    let url = format!("{}/{}/enc_keys", CONFIG.base_url, CONFIG.slave().id);
     let mut responses: Vec<Response> = Vec::new();
 
     responses
@@ -57,8 +60,8 @@ fn validate_key_size(#[case] key_size: &str) {
 #[case::negative_number_of_keys_requested("-8")]
 #[case::alphanumeric_number_of_requested_keys("abc01")]
 fn validate_num_keys(#[case] num_keys: &str) {
-    let client = common::build_client(&CONFIG.master_sae_crt);
-    let url = format!("{}/{}/enc_keys", CONFIG.base_url, CONFIG.slave_sae_id);
+    let client = common::build_client(CONFIG.master(), Side::Master).unwrap();
+    let url = format!("{}/{}/enc_keys", CONFIG.base_url, CONFIG.slave().id);
     let mut responses: Vec<Response> = Vec::new();
 
     responses
@@ -91,12 +94,12 @@ fn validate_num_keys(#[case] num_keys: &str) {
 #[rstest]
 #[case::empty_additional_sae_id_supplied(vec!["additional_sae_1234", " "])]
 #[case::invalid_additional_sae_id_supplied(vec!["additional_sae_1234", "additional_sae_1234"])]
-#[case::duplicate_additional_sae_id_with_slave(vec![CONFIG.slave_sae_id.as_str()])]
-#[case::duplicate_additional_sae_id_with_master(vec![CONFIG.master_sae_id.as_str()])]
+#[case::duplicate_additional_sae_id_with_slave(vec![CONFIG.slave().id.as_str()])]
+#[case::duplicate_additional_sae_id_with_master(vec![CONFIG.master().id.as_str()])]
 #[case::empty_sae_id_list(vec![])]
 fn additional_sae_ids(#[case] additional_slave_sae_ids: std::vec::Vec<&str>) {
-    let client = common::build_client(&CONFIG.master_sae_crt);
-    let url = format!("{}/{}/enc_keys", CONFIG.base_url, CONFIG.slave_sae_id);
+    let client = common::build_client(CONFIG.master(), Side::Master).unwrap();
+    let url = format!("{}/{}/enc_keys", CONFIG.base_url, CONFIG.slave().id);
 
     let response = client
         .post(url)
@@ -123,7 +126,7 @@ fn empty_sae_id_in_path() {
     // update the standard to return 404 when a key is not found.
     // The test can be updated such that it first gets a key and then calls the
     // endpoint, but that is more of a functional test, than validation test.
-    let client = common::build_client(&CONFIG.master_sae_crt);
+    let client = common::build_client(CONFIG.master(), Side::Master).unwrap();
     let enc_keys_url = format!("{}/ /enc_keys", CONFIG.base_url);
     let dec_keys_url = format!("{}/ /dec_keys", CONFIG.base_url);
     let sample_key_id = Uuid::new_v4();
@@ -172,11 +175,11 @@ fn identical_sae_ids() {
     // update the standard to return 404 when a key is not found.
     // The test can be updated such that it first gets a key and then calls the
     // endpoint, but that is more of a functional test, than validation test.
-    let client = common::build_client(&CONFIG.master_sae_crt);
+    let client = common::build_client(CONFIG.master(), Side::Master).unwrap();
     let enc_keys_url =
-        format!("{}/{}/enc_keys", CONFIG.base_url, CONFIG.master_sae_id);
+        format!("{}/{}/enc_keys", CONFIG.base_url, CONFIG.master().id);
     let dec_keys_url =
-        format!("{}/{}/dec_keys", CONFIG.base_url, CONFIG.master_sae_id);
+        format!("{}/{}/dec_keys", CONFIG.slave_base_url, CONFIG.master().id);
     let sample_key_id = Uuid::new_v4();
     let mut responses: Vec<Response> = Vec::new();
 
@@ -218,8 +221,9 @@ fn identical_sae_ids() {
 
 #[test]
 fn key_id() {
-    let client = common::build_client(&CONFIG.slave_sae_crt);
-    let url = format!("{}/{}/dec_keys", CONFIG.base_url, CONFIG.master_sae_id);
+    let client = common::build_client(CONFIG.slave(), Side::Slave).unwrap();
+    let url =
+        format!("{}/{}/dec_keys", CONFIG.slave_base_url, CONFIG.master().id);
     let invalid_key_id = "non-uuid";
     let mut responses: Vec<Response> = Vec::new();
 
@@ -253,105 +257,444 @@ fn key_id() {
     }
 }
 
+#[rstest]
+fn unsupported_methods(
+    #[values(Method::PUT, Method::DELETE, Method::PATCH)]
+    request_method: Method,
+    #[values("status", "enc_keys", "dec_keys")] endpoint: &str,
+) {
+    let (client, url) = endpoint_client_and_url(endpoint);
+
+    let response = client.request(request_method, url).send().unwrap();
+
+    assert!(
+        [StatusCode::METHOD_NOT_ALLOWED, StatusCode::BAD_REQUEST]
+            .contains(&response.status()),
+        "Expected METHOD_NOT_ALLOWED or BAD_REQUEST, returned status: {}",
+        &response.status()
+    );
+
+    assert_rejected(response);
+}
+
+#[rstest]
+#[case::plain_text("text/plain")]
+#[case::form_data("application/x-www-form-urlencoded")]
+#[case::xml("application/xml")]
+fn wrong_content_type(
+    #[case] content_type: &str,
+    #[values("enc_keys", "dec_keys")] endpoint: &str,
+) {
+    let (client, url) = endpoint_client_and_url(endpoint);
+    let body = match endpoint {
+        "enc_keys" => json!({"number": 1}),
+        _ => json!({"key_IDs": [{"key_ID": Uuid::new_v4()}]}),
+    };
+
+    let response = client
+        .post(url)
+        .header(CONTENT_TYPE, content_type)
+        .body(body.to_string())
+        .send()
+        .unwrap();
+
+    assert_rejected(response);
+}
+
+#[rstest]
+#[case::truncated_json(b"{\"number\": 1".to_vec())]
+#[case::array_root(b"[]".to_vec())]
+#[case::string_root(b"\"number\"".to_vec())]
+#[case::number_root(b"1".to_vec())]
+#[case::boolean_root(b"true".to_vec())]
+#[case::null_root(b"null".to_vec())]
+#[case::non_utf8_bytes(b"{\"number\": \"\xff\xfe\xfd\"}".to_vec())]
+fn malformed_body(
+    #[case] body: Vec<u8>,
+    #[values("enc_keys", "dec_keys")] endpoint: &str,
+) {
+    let (client, url) = endpoint_client_and_url(endpoint);
+
+    let response = client
+        .post(url)
+        .header(CONTENT_TYPE, "application/json")
+        .body(body)
+        .send()
+        .unwrap();
+
+    assert_rejected(response);
+}
+
+#[test]
+fn duplicated_json_keys() {
+    let (client, url) = endpoint_client_and_url("enc_keys");
+
+    let response = client
+        .post(url)
+        .header(CONTENT_TYPE, "application/json")
+        .body(r#"{"number": 1, "number": 2}"#)
+        .send()
+        .unwrap();
+
+    assert_rejected(response);
+}
+
+#[rstest]
+fn huge_body(#[values("enc_keys", "dec_keys")] endpoint: &str) {
+    let (client, url) = endpoint_client_and_url(endpoint);
+    // A syntactically valid request of 16 MiB, padded with an unknown field.
+    let body = json!({"number": 1, "padding": "a".repeat(16 * 1024 * 1024)});
+
+    match client
+        .post(url)
+        .header(CONTENT_TYPE, "application/json")
+        .body(body.to_string())
+        .send()
+    {
+        Ok(response) => assert_rejected(response),
+        // The KME may reply and close the connection before the whole body is
+        // sent, in which case the reply cannot be read.
+        Err(e) => assert!(
+            matches!(
+                e,
+                Error::Transport {
+                    connected: true,
+                    ..
+                }
+            ),
+            "Request failed while sending a huge body. Error: {}",
+            e
+        ),
+    }
+}
+
+/// Returns the client of the SAE expected to call the given endpoint, together
+/// with the endpoint's URL.
+fn endpoint_client_and_url(endpoint: &str) -> (Client, String) {
+    match endpoint {
+        "status" | "enc_keys" => (
+            common::build_client(CONFIG.master(), Side::Master).unwrap(),
+            format!("{}/{}/{}", CONFIG.base_url, CONFIG.slave().id, endpoint),
+        ),
+        "dec_keys" => (
+            common::build_client(CONFIG.slave(), Side::Slave).unwrap(),
+            format!(
+                "{}/{}/{}",
+                CONFIG.slave_base_url,
+                CONFIG.master().id,
+                endpoint
+            ),
+        ),
+        _ => panic!("Unknown endpoint '{}'", endpoint),
+    }
+}
+
+/// Asserts that the request was rejected and, if the reply is a
+/// `BAD_REQUEST`, that it contains a valid error message.
+fn assert_rejected(response: Response) {
+    let status = response.status();
+
+    assert!(
+        !status.is_success(),
+        "Success returned on invalid request, returned status: {}",
+        status
+    );
+
+    if status == StatusCode::BAD_REQUEST {
+        let response_text = response.text().unwrap();
+
+        assert!(
+            serde_json::from_str::<ErrorMessage>(&response_text).is_ok(),
+            "Invalid error message format returned. Response: {}",
+            &response_text
+        );
+    }
+}
+
 #[rstest]
 #[case::using_get(Method::GET)]
 #[case::using_post(Method::POST)]
 fn num_keys_requested_equals_returned(#[case] request_method: Method) {
-    let client = common::build_client(&CONFIG.master_sae_crt);
-    let url = format!("{}/{}/enc_keys", CONFIG.base_url, CONFIG.slave_sae_id);
-    let num_keys = 5;
-
-    // Request a key
-    let enc_keys_response = match request_method {
-        Method::GET => client
-            .request(request_method, url)
-            .query(&[("number", num_keys)])
-            .send()
-            .unwrap(),
-        Method::POST => client
-            .request(request_method, url)
-            .json(&json!({ "number": num_keys }))
-            .send()
-            .unwrap(),
-        _ => {
-            panic!("Only 'GET' and 'POST' methods are supported")
+    let client = common::build_client(CONFIG.master(), Side::Master).unwrap();
+    let url = format!("{}/{}/enc_keys", CONFIG.base_url, CONFIG.slave().id);
+
+    for num_keys in CAPABILITIES.key_counts() {
+        if let Some(reason) = CAPABILITIES
+            .unsupported_reason(num_keys, CAPABILITIES.status.key_size)
+        {
+            capabilities::skip(&reason);
+            continue;
         }
-    };
 
-    assert!(enc_keys_response.status().is_success());
+        // Request a key
+        let enc_keys_response = match request_method {
+            Method::GET => client
+                .request(request_method.clone(), &url)
+                .query(&[("number", num_keys)])
+                .send()
+                .unwrap(),
+            Method::POST => client
+                .request(request_method.clone(), &url)
+                .json(&json!({ "number": num_keys }))
+                .send()
+                .unwrap(),
+            _ => {
+                panic!("Only 'GET' and 'POST' methods are supported")
+            }
+        };
 
-    let returned_keys = match enc_keys_response.json::<key::KeyContainer>() {
-        Ok(parsed_body) => parsed_body,
-        Err(e) => {
-            panic!("Invalid response given. Error: {:?}", e);
-        }
-    };
+        assert!(
+            enc_keys_response.status().is_success(),
+            "Failed to request {} keys. Returned status: {}",
+            num_keys,
+            enc_keys_response.status()
+        );
+
+        let returned_keys = match enc_keys_response.json::<key::KeyContainer>()
+        {
+            Ok(parsed_body) => parsed_body,
+            Err(e) => {
+                panic!("Invalid response given. Error: {:?}", e);
+            }
+        };
 
-    assert_eq!(returned_keys.keys.len(), num_keys);
+        assert_eq!(returned_keys.keys.len(), num_keys as usize);
+    }
 }
 
 #[rstest]
 #[case::using_get(Method::GET)]
 #[case::using_post(Method::POST)]
 fn key_body(#[case] request_method: Method) {
-    let client = common::build_client(&CONFIG.master_sae_crt);
-    let url = format!("{}/{}/enc_keys", CONFIG.base_url, CONFIG.slave_sae_id);
-    let num_keys = 3;
-    let key_size_bits = 1024;
-    let key_size_bytes = key_size_bits / 8;
-
-    // Request a key
-    let enc_keys_response = match request_method {
-        Method::GET => client
-            .request(request_method, url)
-            .query(&[("number", 1), ("size", key_size_bits)])
-            .send()
-            .unwrap(),
-        Method::POST => client
-            .request(request_method, url)
-            .json(&json!({ "number": num_keys, "size": key_size_bits}))
-            .send()
-            .unwrap(),
-        _ => {
-            panic!("Only 'GET' and 'POST' methods are supported")
+    let client = common::build_client(CONFIG.master(), Side::Master).unwrap();
+    let url = format!("{}/{}/enc_keys", CONFIG.base_url, CONFIG.slave().id);
+
+    for num_keys in CAPABILITIES.key_counts() {
+        for key_size_bits in CAPABILITIES.key_sizes() {
+            if let Some(reason) =
+                CAPABILITIES.unsupported_reason(num_keys, key_size_bits)
+            {
+                capabilities::skip(&reason);
+                continue;
+            }
+
+            let key_size_bytes = key_size_bits as usize / 8;
+
+            // Request a key
+            let enc_keys_response = match request_method {
+                Method::GET => client
+                    .request(request_method.clone(), &url)
+                    .query(&[("number", num_keys), ("size", key_size_bits)])
+                    .send()
+                    .unwrap(),
+                Method::POST => client
+                    .request(request_method.clone(), &url)
+                    .json(&json!({ "number": num_keys, "size": key_size_bits}))
+                    .send()
+                    .unwrap(),
+                _ => {
+                    panic!("Only 'GET' and 'POST' methods are supported")
+                }
+            };
+
+            assert!(
+                enc_keys_response.status().is_success(),
+                "Failed to request {} keys of {} bits. Returned status: {}",
+                num_keys,
+                key_size_bits,
+                enc_keys_response.status()
+            );
+
+            let returned_keys =
+                match enc_keys_response.json::<key::KeyContainer>() {
+                    Ok(parsed_body) => parsed_body,
+                    Err(e) => {
+                        panic!("Invalid response given. Error: {:?}", e);
+                    }
+                };
+
+            assert_eq!(returned_keys.keys.len(), num_keys as usize);
+
+            for key in &returned_keys.keys {
+                let decoded_key = match key_material::decode(
+                    key.key.as_deref().unwrap_or_default(),
+                ) {
+                    Ok((val, _)) => val,
+                    Err(e) => {
+                        panic!("Failed to decode key value. Error: {}", e)
+                    }
+                };
+
+                assert_eq!(
+                    decoded_key.len(),
+                    key_size_bytes,
+                    "Wrong key length for {}-bit keys",
+                    key_size_bits
+                );
+            }
         }
-    };
-
-    assert!(enc_keys_response.status().is_success());
+    }
+}
 
-    let returned_keys = match enc_keys_response.json::<key::KeyContainer>() {
-        Ok(parsed_body) => parsed_body,
-        Err(e) => {
-            panic!("Invalid response given. Error: {:?}", e);
-        }
-    };
+#[test]
+fn status() {
+    let client = common::build_client(CONFIG.master(), Side::Master).unwrap();
+    let url = format!("{}/{}/status", CONFIG.base_url, CONFIG.slave().id);
 
-    for key in &returned_keys.keys {
-        let decoding_result = base64::engine::general_purpose::STANDARD
-            .decode(key.key.as_ref().unwrap());
+    let parsed_reply = request_status(&client, &url);
 
-        assert!(decoding_result.is_ok());
-        assert_eq!(decoding_result.unwrap().len(), key_size_bytes);
-    }
+    assert_eq!(parsed_reply.master_sae_id, CONFIG.master().id);
+    assert_eq!(parsed_reply.slave_sae_id, CONFIG.slave().id);
 }
 
 #[test]
-fn status() {
-    let client = common::build_client(&CONFIG.master_sae_crt);
-    let url = format!("{}/{}/status", CONFIG.base_url, CONFIG.slave_sae_id);
+fn status_semantics() {
+    let client = common::build_client(CONFIG.master(), Side::Master).unwrap();
+    let url = format!("{}/{}/status", CONFIG.base_url, CONFIG.slave().id);
 
     let response = client.get(&url).send().unwrap();
     assert_eq!(response.status(), StatusCode::OK);
 
-    let body = response.text().unwrap();
-
-    let parsed_reply = match serde_json::from_str::<Status>(&body) {
+    let body = match response.json::<serde_json::Value>() {
         Ok(val) => val,
         Err(e) => {
             panic!("Malformed JSON body returned. Error:'{:?}'", e)
         }
     };
 
-    assert_eq!(parsed_reply.master_sae_id, CONFIG.master_sae_id);
-    assert_eq!(parsed_reply.slave_sae_id, CONFIG.slave_sae_id);
+    let violations: Vec<String> = Rule::ALL
+        .iter()
+        .filter_map(|rule| {
+            rule.check(&body)
+                .err()
+                .map(|violation| format!("'{}': {}", rule, violation))
+        })
+        .collect();
+
+    assert!(
+        violations.is_empty(),
+        "Status violates the following rules:\n{}",
+        violations.join("\n")
+    );
+}
+
+#[test]
+fn status_symmetry() {
+    let master_client =
+        common::build_client(CONFIG.master(), Side::Master).unwrap();
+    let slave_client =
+        common::build_client(CONFIG.slave(), Side::Slave).unwrap();
+    let master_url =
+        format!("{}/{}/status", CONFIG.base_url, CONFIG.slave().id);
+    let slave_url =
+        format!("{}/{}/status", CONFIG.slave_base_url, CONFIG.master().id);
+
+    let master_view = request_status(&master_client, &master_url);
+    let slave_view = request_status(&slave_client, &slave_url);
+
+    // Each reply is given from the perspective of the calling SAE: its own KME
+    // is the source and it is named as the master, so the IDs of the slave's
+    // view are those of the master's view, swapped.
+    let swapped = [
+        (
+            "source_KME_ID",
+            &master_view.source_kme_id,
+            "target_KME_ID",
+            &slave_view.target_kme_id,
+        ),
+        (
+            "target_KME_ID",
+            &master_view.target_kme_id,
+            "source_KME_ID",
+            &slave_view.source_kme_id,
+        ),
+        (
+            "master_SAE_ID",
+            &master_view.master_sae_id,
+            "slave_SAE_ID",
+            &slave_view.slave_sae_id,
+        ),
+        (
+            "slave_SAE_ID",
+            &master_view.slave_sae_id,
+            "master_SAE_ID",
+            &slave_view.master_sae_id,
+        ),
+    ];
+    // The stored key count is left out, as keys may be requested in between.
+    let shared = [
+        ("key_size", master_view.key_size, slave_view.key_size),
+        (
+            "max_key_count",
+            master_view.max_key_count,
+            slave_view.max_key_count,
+        ),
+        (
+            "max_key_per_request",
+            master_view.max_key_per_request,
+            slave_view.max_key_per_request,
+        ),
+        (
+            "max_key_size",
+            master_view.max_key_size,
+            slave_view.max_key_size,
+        ),
+        (
+            "min_key_size",
+            master_view.min_key_size,
+            slave_view.min_key_size,
+        ),
+        (
+            "max_SAE_ID_count",
+            master_view.max_sae_id_count,
+            slave_view.max_sae_id_count,
+        ),
+    ];
+
+    let mut inconsistencies = Vec::new();
+
+    for (master_field, master_val, slave_field, slave_val) in swapped {
+        if master_val != slave_val {
+            inconsistencies.push(format!(
+                "master's {} is '{}' but slave's {} is '{}'",
+                master_field, master_val, slave_field, slave_val
+            ));
+        }
+    }
+
+    for (field, master_val, slave_val) in shared {
+        if master_val != slave_val {
+            inconsistencies.push(format!(
+                "master's {} is {} but slave's is {}",
+                field, master_val, slave_val
+            ));
+        }
+    }
+
+    if CONFIG.is_multi_kme()
+        && master_view.source_kme_id == slave_view.source_kme_id
+    {
+        inconsistencies.push(format!(
+            "two different KMEs are configured, but both report the ID '{}'",
+            master_view.source_kme_id
+        ));
+    }
+
+    assert!(
+        inconsistencies.is_empty(),
+        "The master's and slave's status replies are inconsistent:\n{}",
+        inconsistencies.join("\n")
+    );
+}
+
+fn request_status(client: &Client, url: &str) -> Status {
+    let response = client.get(url).send().unwrap();
+    assert_eq!(response.status(), StatusCode::OK, "GET {}", url);
+
+    match response.json::<Status>() {
+        Ok(val) => val,
+        Err(e) => {
+            panic!("Malformed JSON body returned by '{}'. Error: {:?}", url, e)
+        }
+    }
 }